@@ -0,0 +1,175 @@
+use intcode::isa::Isa;
+use intcode::{parse, Memory};
+
+use crate::Case;
+
+const DAY05: &str = include_str!("../../day05/data.txt");
+
+fn case(name: &str, program: Vec<Memory>, input: &[Memory], isa: Isa) -> Case {
+    Case {
+        name: name.to_string(),
+        program,
+        input: input.to_vec(),
+        isa,
+    }
+}
+
+fn day02() -> Vec<Case> {
+    let mut cases = [
+        "1,9,10,3,2,3,11,0,99,30,40,50",
+        "1,0,0,0,99",
+        "2,3,0,3,99",
+        "2,4,4,5,99,0",
+        "1,1,1,4,99,5,6,0,99",
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, program)| {
+        case(
+            &format!("day02 example {}", i + 1),
+            parse(program),
+            &[],
+            Isa::Day2,
+        )
+    })
+    .collect::<Vec<_>>();
+
+    let mut program = day02::DATA
+        .iter()
+        .map(|&value| value as Memory)
+        .collect::<Vec<_>>();
+    program[1] = 12;
+    program[2] = 2;
+    cases.push(case("day02 data", program, &[], Isa::Day2));
+
+    cases
+}
+
+fn day05() -> Vec<Case> {
+    let mut cases = vec![
+        case("day05 echo", parse("3,0,4,0,99"), &[42], Isa::Day5),
+        case("day05 modes", parse("1002,4,3,4,33"), &[], Isa::Day5),
+        case("day05 negative", parse("1101,100,-1,4,0"), &[], Isa::Day5),
+    ];
+
+    for (i, program) in [
+        "3,9,8,9,10,9,4,9,99,-1,8",
+        "3,9,7,9,10,9,4,9,99,-1,8",
+        "3,3,1108,-1,8,3,4,3,99",
+        "3,3,1107,-1,8,3,4,3,99",
+        "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+    ]
+    .iter()
+    .enumerate()
+    {
+        for input in [7, 8, 9] {
+            cases.push(case(
+                &format!("day05 compare {} with {}", i + 1, input),
+                parse(program),
+                &[input],
+                Isa::Day5,
+            ));
+        }
+    }
+
+    for (i, program) in [
+        "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+        "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+    ]
+    .iter()
+    .enumerate()
+    {
+        for input in [0, 5] {
+            cases.push(case(
+                &format!("day05 jump {} with {}", i + 1, input),
+                parse(program),
+                &[input],
+                Isa::Day5,
+            ));
+        }
+    }
+
+    for input in [1, 5] {
+        cases.push(case(
+            &format!("day05 data with {}", input),
+            parse(DAY05),
+            &[input],
+            Isa::Day5,
+        ));
+    }
+
+    cases
+}
+
+fn day07() -> Vec<Case> {
+    let mut cases = [
+        "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0",
+        "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+        "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0",
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, program)| {
+        case(
+            &format!("day07 amplifier {}", i + 1),
+            parse(program),
+            &[3, 17],
+            Isa::Day5,
+        )
+    })
+    .collect::<Vec<_>>();
+
+    // the feedback loop asks for input again after its first output
+    cases.push(case(
+        "day07 feedback",
+        parse(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        ),
+        &[9, 0],
+        Isa::Day5,
+    ));
+
+    for phase in 0..5 {
+        cases.push(case(
+            &format!("day07 data with phase {}", phase),
+            day07::DATA
+                .iter()
+                .map(|&value| Memory::from(value))
+                .collect(),
+            &[phase, 0],
+            Isa::Day5,
+        ));
+    }
+
+    cases
+}
+
+fn day09() -> Vec<Case> {
+    vec![
+        case(
+            "day09 quine",
+            parse("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"),
+            &[],
+            Isa::Full,
+        ),
+        case(
+            "day09 large product",
+            parse("1102,34915192,34915192,7,4,7,99,0"),
+            &[],
+            Isa::Full,
+        ),
+        case(
+            "day09 large output",
+            parse("104,1125899906842624,99"),
+            &[],
+            Isa::Full,
+        ),
+        case("day09 data", day09::DATA.to_owned(), &[1], Isa::Full),
+    ]
+}
+
+// the examples of the puzzles that introduced each part of the instruction
+// set, plus their inputs
+pub fn examples() -> Vec<Case> {
+    [day02(), day05(), day07(), day09()].concat()
+}
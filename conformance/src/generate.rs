@@ -0,0 +1,230 @@
+use intcode::isa::Isa;
+use intcode::{Error, Memory, Run, CPU};
+
+use crate::Case;
+
+const DATA: usize = 8;
+const INSTRUCTIONS: usize = 24;
+const BODY: usize = 3;
+
+// xorshift, so a seed always gives the same program
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn range(&mut self, low: Memory, high: Memory) -> Memory {
+        low + (self.next() % (high - low + 1) as u64) as Memory
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Operand {
+    // a cell of the data area
+    Data(usize),
+    Immediate(Memory),
+    // a cell of the data area, through the relative base
+    Relative(usize),
+    // the address of an instruction
+    Target(usize),
+}
+
+struct Instruction {
+    opcode: Memory,
+    operands: Vec<Operand>,
+    base: Memory,
+}
+
+impl Instruction {
+    fn len(&self) -> usize {
+        1 + self.operands.len()
+    }
+}
+
+struct Generator {
+    rng: Rng,
+    isa: Isa,
+    code: Vec<Instruction>,
+    base: Memory,
+    inputs: usize,
+}
+
+impl Generator {
+    fn read(&mut self) -> Operand {
+        match (self.isa, self.rng.below(3)) {
+            (Isa::Day2, _) | (_, 0) => Operand::Data(self.rng.below(DATA)),
+            (Isa::Full, 1) => Operand::Relative(self.rng.below(DATA)),
+            _ => Operand::Immediate(self.rng.range(-50, 50)),
+        }
+    }
+
+    fn write(&mut self) -> Operand {
+        match (self.isa, self.rng.below(2)) {
+            (Isa::Full, 1) => Operand::Relative(self.rng.below(DATA)),
+            _ => Operand::Data(self.rng.below(DATA)),
+        }
+    }
+
+    fn push(&mut self, opcode: Memory, operands: Vec<Operand>) {
+        self.code.push(Instruction {
+            opcode,
+            operands,
+            base: self.base,
+        });
+    }
+
+    // an instruction that only touches the data area and the output
+    fn plain(&mut self) {
+        let opcode = match self.isa {
+            Isa::Day2 => [1, 2][self.rng.below(2)],
+            _ => [1, 2, 4, 7, 8][self.rng.below(5)],
+        };
+
+        let operands = if opcode == 4 {
+            vec![self.read()]
+        } else {
+            vec![self.read(), self.read(), self.write()]
+        };
+        self.push(opcode, operands);
+    }
+
+    // jumps only go forward over a body without jumps, inputs or base
+    // changes, so every program halts and the base is known everywhere
+    fn jump(&mut self) {
+        let opcode = [5, 6][self.rng.below(2)];
+        let condition = self.read();
+        let body = 1 + self.rng.below(BODY);
+        let target = Operand::Target(self.code.len() + 1 + body);
+
+        self.push(opcode, vec![condition, target]);
+        for _ in 0..body {
+            self.plain();
+        }
+    }
+
+    fn generate(mut self, limit: usize) -> (Vec<Memory>, usize) {
+        while self.code.len() < INSTRUCTIONS {
+            match (self.isa, self.rng.below(8)) {
+                (Isa::Day2, _) => self.plain(),
+                (_, 0 | 1) => self.jump(),
+                (_, 2) if self.inputs < limit => {
+                    let operand = self.write();
+                    self.push(3, vec![operand]);
+                    self.inputs += 1;
+                }
+                (Isa::Full, 3) => {
+                    let offset = self.rng.range(-4, 12);
+                    self.push(9, vec![Operand::Immediate(offset)]);
+                    self.base += offset;
+                }
+                _ => self.plain(),
+            }
+        }
+
+        // now and then the program ends on an invalid opcode
+        let end = if self.rng.below(10) == 0 { 98 } else { 99 };
+        self.push(end, vec![]);
+
+        let mut addresses = vec![0];
+        for instruction in &self.code {
+            addresses.push(addresses.last().unwrap() + instruction.len());
+        }
+        let data = *addresses.last().unwrap();
+
+        let mut program = vec![];
+        for instruction in &self.code {
+            let mut opcode = instruction.opcode;
+            let mut weight = 100;
+            let mut operands = vec![];
+            for operand in &instruction.operands {
+                let (mode, value) = match *operand {
+                    Operand::Data(index) => (0, (data + index) as Memory),
+                    Operand::Immediate(value) => (1, value),
+                    Operand::Relative(index) => (2, (data + index) as Memory - instruction.base),
+                    Operand::Target(index) => (1, addresses[index] as Memory),
+                };
+                opcode += mode * weight;
+                weight *= 10;
+                operands.push(value);
+            }
+            program.push(opcode);
+            program.extend(operands);
+        }
+
+        let (low, high) = match self.isa {
+            Isa::Day2 => (0, 50),
+            _ => (-50, 50),
+        };
+        for _ in 0..DATA {
+            program.push(self.rng.range(low, high));
+        }
+
+        (program, self.inputs)
+    }
+}
+
+// the values must fit in every interpreter, so a program whose run
+// overflows an i64 is rejected
+fn accepted(case: &Case) -> bool {
+    let mut cpu = CPU::from_words(
+        case.program.iter().map(|&value| value as i64).collect(),
+        0,
+        None,
+    );
+    cpu.set_isa(case.isa);
+    cpu.extend_input(case.input.iter().map(|&value| value as i64));
+
+    loop {
+        match cpu.run() {
+            Ok(Run::Output(_)) => {}
+            Ok(Run::NeedInput) | Ok(Run::Halt) => return true,
+            Err(Error::Overflow(..)) => return false,
+            Err(_) => return true,
+        }
+    }
+}
+
+// a random valid program for the instruction set, with its input
+pub fn program(seed: u64, isa: Isa) -> Case {
+    let limit = match isa {
+        Isa::Day2 => 0,
+        Isa::Day5 => 1,
+        Isa::Full => 3,
+    };
+
+    let mut rng = Rng::new(seed);
+    loop {
+        let generator = Generator {
+            rng: Rng::new(rng.next()),
+            isa,
+            code: vec![],
+            base: 0,
+            inputs: 0,
+        };
+        let (program, inputs) = generator.generate(limit);
+        let input = (0..inputs).map(|_| rng.range(-50, 50)).collect();
+
+        let case = Case {
+            name: format!("{:?} program {}", isa, seed),
+            program,
+            input,
+            isa,
+        };
+        if accepted(&case) {
+            return case;
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use intcode::debugger::{Debugger, Event};
+use intcode::disasm::disassemble;
+use intcode::isa::Isa;
+use intcode::{Memory, Run, CPU};
+
+pub mod corpus;
+pub mod generate;
+
+const TRACE_LINES: usize = 24;
+const TRACE_STEPS: usize = 100_000;
+
+// what went wrong, without the details each interpreter reports differently
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fault {
+    InvalidOpcode,
+    InvalidMode,
+    ImmediateWrite,
+    NoInput,
+    EOF,
+    Address,
+    Overflow,
+    Panic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<Memory>,
+    pub memory: Vec<Memory>,
+    pub fault: Option<Fault>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub program: Vec<Memory>,
+    pub input: Vec<Memory>,
+    pub isa: Isa,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpreter {
+    Day02,
+    Day05,
+    Day07,
+    Day09,
+    Intcode,
+}
+
+impl Interpreter {
+    pub const ALL: [Interpreter; 5] = [
+        Interpreter::Day02,
+        Interpreter::Day05,
+        Interpreter::Day07,
+        Interpreter::Day09,
+        Interpreter::Intcode,
+    ];
+
+    pub fn supports(self, case: &Case) -> bool {
+        match self {
+            Interpreter::Day02 => case.isa == Isa::Day2 && case.input.is_empty(),
+            Interpreter::Day05 => case.isa != Isa::Full && case.input.len() <= 1,
+            Interpreter::Day07 => case.isa != Isa::Full,
+            Interpreter::Day09 | Interpreter::Intcode => true,
+        }
+    }
+
+    // a panic is reported as a fault, so one broken interpreter does not
+    // stop the whole run
+    pub fn run(self, case: &Case) -> Outcome {
+        let len = case.program.len();
+        panic::catch_unwind(AssertUnwindSafe(|| match self {
+            Interpreter::Day02 => day02(case),
+            Interpreter::Day05 => day05(case),
+            Interpreter::Day07 => day07(case),
+            Interpreter::Day09 => day09(case),
+            Interpreter::Intcode => intcode(case),
+        }))
+        .unwrap_or_else(|_| Outcome {
+            output: vec![],
+            memory: vec![0; len],
+            fault: Some(Fault::Panic),
+        })
+    }
+}
+
+impl fmt::Display for Interpreter {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Interpreter::Day02 => write!(fmt, "day02 execute"),
+            Interpreter::Day05 => write!(fmt, "day05 execute"),
+            Interpreter::Day07 => write!(fmt, "day07 intcode::CPU"),
+            Interpreter::Day09 => write!(fmt, "day09 simple::intcode::CPU"),
+            Interpreter::Intcode => write!(fmt, "intcode::CPU"),
+        }
+    }
+}
+
+fn day02(case: &Case) -> Outcome {
+    let mut memory = case
+        .program
+        .iter()
+        .map(|&value| value as usize)
+        .collect::<Vec<_>>();
+    let fault = day02::execute(&mut memory)
+        .err()
+        .map(|_| Fault::InvalidOpcode);
+
+    Outcome {
+        output: vec![],
+        memory: memory
+            .into_iter()
+            .map(|value| value as i64 as Memory)
+            .collect(),
+        fault,
+    }
+}
+
+fn day05(case: &Case) -> Outcome {
+    let mut memory = case
+        .program
+        .iter()
+        .map(|&value| value as i64)
+        .collect::<Vec<_>>();
+    let (output, fault) =
+        match day05::execute(case.input.first().map(|&value| value as i64), &mut memory) {
+            Ok(output) => (output, None),
+            Err(e) => (
+                vec![],
+                Some(match e {
+                    day05::Error::InvalidOpcode(..) => Fault::InvalidOpcode,
+                    day05::Error::InvalidOpcodeMode(..) => Fault::ImmediateWrite,
+                    day05::Error::InvalidOpcodeModeIndex(..)
+                    | day05::Error::InvalidOpcodeModeValue(..) => Fault::InvalidMode,
+                    day05::Error::EOF => Fault::EOF,
+                    day05::Error::NoInput(_) => Fault::NoInput,
+                }),
+            ),
+        };
+
+    Outcome {
+        output: output.into_iter().map(Memory::from).collect(),
+        memory: memory.into_iter().map(Memory::from).collect(),
+        fault,
+    }
+}
+
+fn day07(case: &Case) -> Outcome {
+    use day07::intcode::{Error, Step, CPU};
+
+    let mut cpu = CPU::new(
+        case.program.iter().map(|&value| value as i64).collect(),
+        0,
+        None,
+    );
+    let mut input = case.input.iter().map(|&value| value as i64);
+    let mut output = vec![];
+    let fault = loop {
+        match cpu.run() {
+            Ok(Step::NeedInput) => match input.next() {
+                Some(value) => cpu.set_input(Some(value)),
+                None => break Some(Fault::NoInput),
+            },
+            Ok(Step::Output(value)) => output.push(Memory::from(value)),
+            Ok(Step::Halt) => break None,
+            Ok(Step::Continue) => unreachable!(),
+            Err(Error::InvalidOpcode(..)) => break Some(Fault::InvalidOpcode),
+            Err(Error::InvalidOpcodeMode(..)) => break Some(Fault::ImmediateWrite),
+            Err(Error::InvalidOpcodeModeIndex(..) | Error::InvalidOpcodeModeValue(..)) => {
+                break Some(Fault::InvalidMode)
+            }
+            Err(Error::EOF) => break Some(Fault::EOF),
+            Err(Error::NoInput(_)) => break Some(Fault::NoInput),
+        }
+    };
+
+    Outcome {
+        output,
+        memory: (0..case.program.len())
+            .map(|address| Memory::from(cpu.memory(address)))
+            .collect(),
+        fault,
+    }
+}
+
+fn day09(case: &Case) -> Outcome {
+    use day09::simple::intcode::{Error, Step, CPU};
+
+    let mut cpu = CPU::new(case.program.to_owned(), 0, None);
+    let mut input = case.input.iter().copied();
+    let mut output = vec![];
+    let fault = loop {
+        match cpu.run() {
+            Ok(Step::NeedInput) => match input.next() {
+                Some(value) => cpu.set_input(Some(value)),
+                None => break Some(Fault::NoInput),
+            },
+            Ok(Step::Output(value)) => output.push(value),
+            Ok(Step::Halt) => break None,
+            Ok(Step::Continue) => unreachable!(),
+            Err(Error::InvalidOpcode(..)) => break Some(Fault::InvalidOpcode),
+            Err(Error::InvalidOpcodeMode(..)) => break Some(Fault::ImmediateWrite),
+            Err(Error::InvalidOpcodeModeIndex(..) | Error::InvalidOpcodeModeValue(..)) => {
+                break Some(Fault::InvalidMode)
+            }
+            Err(Error::EOF) => break Some(Fault::EOF),
+        }
+    };
+
+    Outcome {
+        output,
+        memory: (0..case.program.len())
+            .map(|address| cpu.memory(address))
+            .collect(),
+        fault,
+    }
+}
+
+fn intcode(case: &Case) -> Outcome {
+    use intcode::{DecodeOpcodeError, Error};
+
+    let mut cpu = CPU::new(case.program.to_owned(), 0, None);
+    cpu.set_isa(case.isa);
+    let mut input = case.input.iter().copied();
+    let mut output = vec![];
+    let fault = loop {
+        match cpu.run() {
+            Ok(Run::NeedInput) => match input.next() {
+                Some(value) => cpu.push_input(value),
+                None => break Some(Fault::NoInput),
+            },
+            Ok(Run::Output(value)) => output.push(value),
+            Ok(Run::Halt) => break None,
+            Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(..))) => {
+                break Some(Fault::InvalidOpcode)
+            }
+            Err(
+                Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcodeModeValue(..))
+                | Error::InvalidOpcodeModeValue(..),
+            ) => break Some(Fault::InvalidMode),
+            Err(Error::InvalidOpcodeMode(..)) => break Some(Fault::ImmediateWrite),
+            Err(Error::NegativeAddress(..) | Error::AddressOutOfRange(..)) => {
+                break Some(Fault::Address)
+            }
+            Err(Error::Overflow(..)) => break Some(Fault::Overflow),
+            Err(Error::EOF) => break Some(Fault::EOF),
+        }
+    };
+
+    Outcome {
+        output,
+        memory: (0..case.program.len())
+            .map(|address| cpu.memory(address))
+            .collect(),
+        fault,
+    }
+}
+
+// the first point where two outcomes disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    Output(usize, Option<Memory>, Option<Memory>),
+    Memory(usize, Memory, Memory),
+    Fault(Option<Fault>, Option<Fault>),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let value = |value: &Option<Memory>| match value {
+            Some(value) => value.to_string(),
+            None => "nothing".to_string(),
+        };
+
+        match self {
+            Difference::Output(index, expected, actual) => write!(
+                fmt,
+                "output {}: expected {}, got {}",
+                index,
+                value(expected),
+                value(actual)
+            ),
+            Difference::Memory(address, expected, actual) => write!(
+                fmt,
+                "memory at {}: expected {}, got {}",
+                address, expected, actual
+            ),
+            Difference::Fault(expected, actual) => {
+                write!(fmt, "fault: expected {:?}, got {:?}", expected, actual)
+            }
+        }
+    }
+}
+
+pub fn compare(expected: &Outcome, actual: &Outcome) -> Option<Difference> {
+    let outputs = expected.output.len().max(actual.output.len());
+    if let Some(index) =
+        (0..outputs).find(|&index| expected.output.get(index) != actual.output.get(index))
+    {
+        return Some(Difference::Output(
+            index,
+            expected.output.get(index).copied(),
+            actual.output.get(index).copied(),
+        ));
+    }
+
+    if let Some((address, (expected, actual))) = expected
+        .memory
+        .iter()
+        .zip(&actual.memory)
+        .enumerate()
+        .find(|(_, (expected, actual))| expected != actual)
+    {
+        return Some(Difference::Memory(address, *expected, *actual));
+    }
+
+    if expected.fault != actual.fault {
+        return Some(Difference::Fault(expected.fault, actual.fault));
+    }
+
+    None
+}
+
+pub struct Divergence {
+    pub case: Case,
+    pub interpreter: Interpreter,
+    pub difference: Difference,
+}
+
+impl Divergence {
+    // the last steps of the reference run, up to the diverging output
+    pub fn trace(&self) -> Vec<String> {
+        let mut cpu = CPU::new(self.case.program.to_owned(), 0, None);
+        cpu.set_isa(self.case.isa);
+        cpu.extend_input(self.case.input.iter().copied());
+
+        let stop = match self.difference {
+            Difference::Output(index, ..) => Some(index + 1),
+            _ => None,
+        };
+
+        let mut debugger = Debugger::new(cpu);
+        let mut lines = VecDeque::new();
+        let mut outputs = 0;
+        for _ in 0..TRACE_STEPS {
+            let ip = debugger.ip();
+            lines.push_back(format!(
+                "{:>6}  base {:<6} {}",
+                ip,
+                debugger.base(),
+                debugger.instruction(ip)
+            ));
+            if lines.len() > TRACE_LINES {
+                lines.pop_front();
+            }
+
+            match debugger.step() {
+                Ok(Event::Output(_)) => {
+                    outputs += 1;
+                    if Some(outputs) == stop {
+                        break;
+                    }
+                }
+                Ok(Event::Step) | Ok(Event::Breakpoint(_)) | Ok(Event::Watchpoint { .. }) => {}
+                Ok(Event::NeedInput) | Ok(Event::Halt) => break,
+                Err(e) => {
+                    lines.push_back(format!("        {}", e));
+                    break;
+                }
+            }
+        }
+
+        lines.into()
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        writeln!(
+            fmt,
+            "{}: {} diverges from {}",
+            self.case.name,
+            self.interpreter,
+            Interpreter::Intcode
+        )?;
+        writeln!(fmt, "  {}", self.difference)?;
+        writeln!(fmt, "input: {:?}", self.case.input)?;
+        writeln!(fmt, "trace:")?;
+        for line in self.trace() {
+            writeln!(fmt, "{}", line)?;
+        }
+        writeln!(fmt, "program:")?;
+        write!(fmt, "{}", disassemble(&self.case.program))
+    }
+}
+
+impl fmt::Debug for Divergence {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt::Display::fmt(self, fmt)
+    }
+}
+
+// runs the case on every interpreter that supports it, the intcode crate is
+// the reference
+pub fn check(case: &Case) -> Result<(), Box<Divergence>> {
+    let expected = Interpreter::Intcode.run(case);
+
+    for interpreter in Interpreter::ALL {
+        if interpreter == Interpreter::Intcode || !interpreter.supports(case) {
+            continue;
+        }
+
+        let mut actual = interpreter.run(case);
+        // day05 drops the output on a fault, so it is compared only on success
+        if interpreter == Interpreter::Day05 && actual.fault.is_some() {
+            actual.output = expected.output.to_owned();
+        }
+
+        if let Some(difference) = compare(&expected, &actual) {
+            return Err(Box::new(Divergence {
+                case: case.to_owned(),
+                interpreter,
+                difference,
+            }));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_examples() {
+        for case in corpus::examples() {
+            if let Err(divergence) = check(&case) {
+                panic!("{}", divergence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated() {
+        for isa in [Isa::Day2, Isa::Day5, Isa::Full] {
+            for seed in 0..300 {
+                if let Err(divergence) = check(&generate::program(seed, isa)) {
+                    panic!("{}", divergence);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let outcome = |output: Vec<Memory>, memory: Vec<Memory>, fault| Outcome {
+            output,
+            memory,
+            fault,
+        };
+
+        let expected = outcome(vec![1, 2], vec![4, 0, 99], None);
+        assert_eq!(compare(&expected, &expected.to_owned()), None);
+        assert_eq!(
+            compare(&expected, &outcome(vec![1], vec![4, 0, 99], None)),
+            Some(Difference::Output(1, Some(2), None))
+        );
+        assert_eq!(
+            compare(&expected, &outcome(vec![1, 2], vec![4, 1, 99], None)),
+            Some(Difference::Memory(1, 0, 1))
+        );
+        assert_eq!(
+            compare(
+                &expected,
+                &outcome(vec![1, 2], vec![4, 0, 99], Some(Fault::Panic))
+            ),
+            Some(Difference::Fault(None, Some(Fault::Panic)))
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let divergence = Divergence {
+            case: Case {
+                name: "report".to_string(),
+                program: vec![104, 1, 1101, 2, 3, 9, 4, 9, 99, 0],
+                input: vec![],
+                isa: Isa::Day5,
+            },
+            interpreter: Interpreter::Day07,
+            difference: Difference::Output(1, Some(5), Some(6)),
+        };
+
+        let report = divergence.to_string();
+        assert!(report.starts_with("report: day07 intcode::CPU diverges from intcode::CPU\n"));
+        assert!(report.contains("  output 1: expected 5, got 6\n"));
+        assert_eq!(
+            divergence.trace(),
+            vec![
+                "     0  base 0      out #1",
+                "     2  base 0      add #2, #3, [9]",
+                "     6  base 0      out [9]",
+            ]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::env;
+use std::process;
+
+use conformance::{check, corpus, generate};
+use intcode::isa::Isa;
+
+fn main() {
+    let programs = env::args()
+        .nth(1)
+        .map_or(1000, |n| n.parse().expect("usage: conformance [programs]"));
+
+    let mut cases = corpus::examples();
+    for isa in [Isa::Day2, Isa::Day5, Isa::Full] {
+        cases.extend((0..programs).map(|seed| generate::program(seed, isa)));
+    }
+
+    for case in &cases {
+        if let Err(divergence) = check(case) {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+    }
+
+    println!("ok: {} cases", cases.len());
+}
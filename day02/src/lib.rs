@@ -4,37 +4,47 @@ extern crate test;
 #[macro_use]
 extern crate lazy_static;
 
+use std::iter::FromIterator;
+
+use intcode::isa::Isa;
+use intcode::patch::Patch;
+use intcode::{DecodeOpcodeError, Error, Run, CPU};
+
 lazy_static! {
     pub static ref DATA: Vec<usize> = include_str!("../data.txt")
         .trim()
         .split(',')
         .map(|s| s.parse().unwrap())
         .collect();
+    static ref PROGRAM: Vec<i64> = DATA.iter().map(|&value| value as i64).collect();
 }
 
 pub fn execute(memory: &mut [usize]) -> Result<usize, usize> {
-    let mut index = 0;
-    loop {
-        match memory[index] {
-            1 => {
-                memory[memory[index + 3]] = memory[memory[index + 1]] + memory[memory[index + 2]];
-                index += 4;
-            }
-            2 => {
-                memory[memory[index + 3]] = memory[memory[index + 1]] * memory[memory[index + 2]];
-                index += 4;
-            }
-            99 => break Ok(memory[0]),
-            _ => break Err(index),
-        }
+    let mut cpu = CPU::from_words(memory.iter().map(|&value| value as i64).collect(), 0, None);
+    let result = run(&mut cpu);
+
+    memory
+        .iter_mut()
+        .enumerate()
+        .for_each(|(address, value)| *value = cpu.memory(address) as usize);
+
+    result
+}
+
+fn run(cpu: &mut CPU<i64>) -> Result<usize, usize> {
+    cpu.set_isa(Isa::Day2);
+
+    match cpu.run() {
+        Ok(Run::Halt) => Ok(cpu.memory(0) as usize),
+        Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(_, ip))) => Err(ip),
+        result => panic!("invalid program: {:?}", result),
     }
 }
 
 fn execute_with_input(noun: usize, verb: usize) -> Result<usize, usize> {
-    let mut memory = DATA.clone();
-    memory[1] = noun;
-    memory[2] = verb;
-    execute(&mut memory)
+    let mut cpu = CPU::from_words(PROGRAM.clone(), 0, None);
+    cpu.patch(&Patch::from_iter(vec![(1, noun as i64), (2, verb as i64)]));
+    run(&mut cpu)
 }
 
 pub fn part_1() -> usize {
@@ -4,7 +4,8 @@ extern crate test;
 #[macro_use]
 extern crate lazy_static;
 
-const SEPARATOR: char = ',';
+use intcode::isa::Isa;
+use intcode::{DecodeOpcodeError, Run, CPU};
 
 #[derive(Debug)]
 pub enum OpcodeMode {
@@ -26,128 +27,64 @@ pub enum Error {
 }
 
 fn parse(data: &str) -> Vec<Memory> {
-    data.trim()
-        .split(SEPARATOR)
-        .map(|s| s.parse().unwrap())
-        .collect::<Vec<Memory>>()
+    intcode::parse(data)
 }
 
 lazy_static! {
     static ref DATA: Vec<Memory> = parse(include_str!("../data.txt"));
 }
 
+// the first mode digit the day 5 cpu does not know
+fn mode_value(opcode: Opcode) -> Opcode {
+    [100, 1000, 10000]
+        .iter()
+        .map(|weight| opcode / weight % 10)
+        .find(|&mode| mode > 1)
+        .unwrap_or_default()
+}
+
+fn error(cpu: &CPU<Memory>, error: intcode::Error<Memory>) -> Error {
+    match error {
+        intcode::Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(opcode, ip)) => {
+            Error::InvalidOpcode(opcode as Opcode, ip)
+        }
+        intcode::Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcodeModeValue(opcode, _)) => {
+            Error::InvalidOpcodeModeValue(opcode as Opcode, mode_value(opcode as Opcode))
+        }
+        intcode::Error::InvalidOpcodeMode(_, _, ip) => {
+            Error::InvalidOpcodeMode(cpu.memory(ip) as Opcode, OpcodeMode::Immediate)
+        }
+        intcode::Error::EOF => Error::EOF,
+        e => panic!("invalid program: {}", e),
+    }
+}
+
 pub fn execute(
     mut input: Option<Memory>,
     istructions: &mut [Memory],
 ) -> Result<Vec<Memory>, Error> {
-    let mut result = vec![];
+    let mut cpu = CPU::from_words(istructions.to_vec(), 0, None);
+    cpu.set_isa(Isa::Day5);
 
-    let mut ip = 0;
-    loop {
-        let opcode = istructions.get(ip).ok_or_else(|| Error::EOF)?.to_owned() as Opcode;
-
-        let opcode_mode = |i| match i {
-            0 => Ok(OpcodeMode::Positional),
-            1 => Ok(OpcodeMode::Immediate),
-            _ => Err(Error::InvalidOpcodeModeValue(opcode, i)),
-        };
-
-        let mode = |i| match i {
-            1 => opcode_mode(opcode / 100 % 10),
-            2 => opcode_mode(opcode / 1000 % 10),
-            3 => opcode_mode(opcode / 10000 % 10),
-            _ => Err(Error::InvalidOpcodeModeIndex(opcode, i)),
-        };
-
-        let write = |is: &mut [Memory], i, m, v| match m {
-            OpcodeMode::Positional => {
-                is[is[i] as usize] = v;
-                Ok(())
-            }
-            OpcodeMode::Immediate => Err(Error::InvalidOpcodeMode(opcode, m)),
-        };
-
-        let read = |is: &[Memory], i, m| -> Result<Memory, Error> {
-            match m {
-                OpcodeMode::Positional => Ok(is[is[i] as usize]),
-                OpcodeMode::Immediate => Ok(is[i] as Memory),
-            }
-        };
-
-        match opcode % 100 {
-            1 => {
-                write(
-                    istructions,
-                    ip + 3,
-                    mode(3)?,
-                    read(istructions, ip + 1, mode(1)?)? + read(istructions, ip + 2, mode(2)?)?,
-                )?;
-                ip += 4;
-            }
-            2 => {
-                write(
-                    istructions,
-                    ip + 3,
-                    mode(3)?,
-                    read(istructions, ip + 1, mode(1)?)? * read(istructions, ip + 2, mode(2)?)?,
-                )?;
-                ip += 4;
-            }
-            3 => {
-                write(
-                    istructions,
-                    ip + 1,
-                    OpcodeMode::Positional,
-                    input.take().ok_or_else(|| Error::NoInput(ip))?,
-                )?;
-                ip += 2;
-            }
-            4 => {
-                result.push(read(istructions, ip + 1, mode(1)?)?);
-                ip += 2;
-            }
-            5 => {
-                ip = if read(istructions, ip + 1, mode(1)?)? != 0 {
-                    read(istructions, ip + 2, mode(2)?)? as usize
-                } else {
-                    ip + 3
-                }
-            }
-            6 => {
-                ip = if read(istructions, ip + 1, mode(1)?)? == 0 {
-                    read(istructions, ip + 2, mode(2)?)? as usize
-                } else {
-                    ip + 3
-                }
-            }
-            7 => {
-                let value = if read(istructions, ip + 1, mode(1)?)?
-                    < read(istructions, ip + 2, mode(2)?)?
-                {
-                    1
-                } else {
-                    0
-                };
-
-                write(istructions, ip + 3, mode(3)?, value)?;
-                ip += 4;
-            }
-            8 => {
-                let value = if read(istructions, ip + 1, mode(1)?)?
-                    == read(istructions, ip + 2, mode(2)?)?
-                {
-                    1
-                } else {
-                    0
-                };
-
-                write(istructions, ip + 3, mode(3)?, value)?;
-                ip += 4;
-            }
-            99 => return Ok(result),
-            _ => return Err(Error::InvalidOpcode(opcode, ip)),
+    let mut result = vec![];
+    let outcome = loop {
+        match cpu.run() {
+            Ok(Run::Output(value)) => result.push(value),
+            Ok(Run::NeedInput) => match input.take() {
+                Some(value) => cpu.push_input(value),
+                None => break Err(Error::NoInput(cpu.ip())),
+            },
+            Ok(Run::Halt) => break Ok(result),
+            Err(e) => break Err(error(&cpu, e)),
         }
-    }
+    };
+
+    istructions
+        .iter_mut()
+        .enumerate()
+        .for_each(|(address, value)| *value = cpu.memory(address));
+
+    outcome
 }
 
 pub fn part_1() -> Memory {
@@ -233,6 +170,26 @@ mod tests {
         assert_eq!(execute(Some(8), &mut istructions).expect("err"), vec![1000]);
     }
 
+    #[test]
+    fn test_errors() {
+        let run = |program: &str, input| execute(input, &mut parse(program));
+
+        assert!(matches!(run("3,0,99", None), Err(Error::NoInput(0))));
+        assert!(matches!(
+            run("3,0,109,1,99", Some(1)),
+            Err(Error::InvalidOpcode(109, 2))
+        ));
+        assert!(matches!(
+            run("204,0,99", None),
+            Err(Error::InvalidOpcodeModeValue(204, 2))
+        ));
+        assert!(matches!(
+            run("11101,1,1,3,99", None),
+            Err(Error::InvalidOpcodeMode(11101, OpcodeMode::Immediate))
+        ));
+        assert!(matches!(run("1101,1,1,3", None), Err(Error::EOF)));
+    }
+
     #[bench]
     fn bench_part_1(b: &mut Bencher) {
         b.iter(part_1);
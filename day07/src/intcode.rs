@@ -1,6 +1,9 @@
 use std::sync::mpsc::{Receiver, RecvError, SendError, Sender};
 use std::thread;
 
+use ::intcode::isa::Isa;
+use ::intcode::DecodeOpcodeError;
+
 #[derive(Debug)]
 pub enum OpcodeMode {
     Positional,
@@ -35,155 +38,74 @@ pub enum Step {
     Halt,
 }
 
+// the day 5 instruction set on the shared cpu
 pub struct CPU {
-    memory: Vec<Memory>,
-    ip: usize,
-    input: Option<Memory>,
+    cpu: ::intcode::CPU<Memory>,
+}
+
+// the first mode digit the day 5 cpu does not know
+fn mode_value(opcode: Opcode) -> Opcode {
+    [100, 1000, 10000]
+        .iter()
+        .map(|weight| opcode / weight % 10)
+        .find(|&mode| mode > 1)
+        .unwrap_or_default()
 }
 
 impl CPU {
     pub fn new(memory: Vec<Memory>, ip: usize, input: Option<Memory>) -> Self {
-        Self { memory, ip, input }
+        let mut cpu = ::intcode::CPU::from_words(memory, ip, input);
+        cpu.set_isa(Isa::Day5);
+
+        Self { cpu }
     }
 
-    pub fn input(&self) -> Option<Memory> {
-        self.input
+    pub fn ip(&self) -> usize {
+        self.cpu.ip()
     }
 
-    pub fn set_input(&mut self, input: Option<Memory>) {
-        self.input = input;
+    pub fn memory(&self, address: usize) -> Memory {
+        self.cpu.memory(address)
     }
 
-    pub fn step(&mut self) -> Result<Step, Error> {
-        let memory = self.memory.as_mut_slice();
-
-        let opcode = memory.get(self.ip).ok_or_else(|| Error::EOF)?.to_owned() as Opcode;
-
-        let opcode_mode = |i| match i {
-            0 => Ok(OpcodeMode::Positional),
-            1 => Ok(OpcodeMode::Immediate),
-            _ => Err(Error::InvalidOpcodeModeValue(opcode, i)),
-        };
-
-        let mode = |i| match i {
-            1 => opcode_mode(opcode / 100 % 10),
-            2 => opcode_mode(opcode / 1000 % 10),
-            3 => opcode_mode(opcode / 10000 % 10),
-            _ => Err(Error::InvalidOpcodeModeIndex(opcode, i)),
-        };
-
-        let write = |is: &mut [Memory], i, m, v| match m {
-            OpcodeMode::Positional => {
-                is[is[i] as usize] = v;
-                Ok(())
-            }
-            OpcodeMode::Immediate => Err(Error::InvalidOpcodeMode(opcode, m)),
-        };
+    pub fn input(&self) -> Option<Memory> {
+        self.cpu.input()
+    }
 
-        let read = |is: &[Memory], i, m| -> Result<Memory, Error> {
-            match m {
-                OpcodeMode::Positional => Ok(is[is[i] as usize]),
-                OpcodeMode::Immediate => Ok(is[i] as Memory),
-            }
-        };
-
-        match opcode % 100 {
-            1 => {
-                write(
-                    memory,
-                    self.ip + 3,
-                    mode(3)?,
-                    read(memory, self.ip + 1, mode(1)?)? + read(memory, self.ip + 2, mode(2)?)?,
-                )?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            2 => {
-                write(
-                    memory,
-                    self.ip + 3,
-                    mode(3)?,
-                    read(memory, self.ip + 1, mode(1)?)? * read(memory, self.ip + 2, mode(2)?)?,
-                )?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            3 => {
-                if let Some(input) = self.input {
-                    write(memory, self.ip + 1, OpcodeMode::Positional, input)?;
-                    self.input = None;
-                    self.ip += 2;
-
-                    Ok(Step::Continue)
-                } else {
-                    Ok(Step::NeedInput)
-                }
-            }
-            4 => {
-                let output = read(memory, self.ip + 1, mode(1)?)?;
-                self.ip += 2;
+    pub fn set_input(&mut self, input: Option<Memory>) {
+        self.cpu.set_input(input);
+    }
 
-                Ok(Step::Output(output))
-            }
-            5 => {
-                self.ip = if read(memory, self.ip + 1, mode(1)?)? != 0 {
-                    read(memory, self.ip + 2, mode(2)?)? as usize
-                } else {
-                    self.ip + 3
-                };
-
-                Ok(Step::Continue)
-            }
-            6 => {
-                self.ip = if read(memory, self.ip + 1, mode(1)?)? == 0 {
-                    read(memory, self.ip + 2, mode(2)?)? as usize
-                } else {
-                    self.ip + 3
-                };
-
-                Ok(Step::Continue)
+    fn error(&self, error: ::intcode::Error<Memory>) -> Error {
+        match error {
+            ::intcode::Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(opcode, ip)) => {
+                Error::InvalidOpcode(opcode as Opcode, ip)
             }
-            7 => {
-                let value = if read(memory, self.ip + 1, mode(1)?)?
-                    < read(memory, self.ip + 2, mode(2)?)?
-                {
-                    1
-                } else {
-                    0
-                };
-
-                write(memory, self.ip + 3, mode(3)?, value)?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
+            ::intcode::Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcodeModeValue(
+                opcode,
+                _,
+            )) => Error::InvalidOpcodeModeValue(opcode as Opcode, mode_value(opcode as Opcode)),
+            ::intcode::Error::InvalidOpcodeMode(_, _, ip) => {
+                Error::InvalidOpcodeMode(self.cpu.memory(ip) as Opcode, OpcodeMode::Immediate)
             }
-            8 => {
-                let value = if read(memory, self.ip + 1, mode(1)?)?
-                    == read(memory, self.ip + 2, mode(2)?)?
-                {
-                    1
-                } else {
-                    0
-                };
-
-                write(memory, self.ip + 3, mode(3)?, value)?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            99 => Ok(Step::Halt),
+            ::intcode::Error::EOF => Error::EOF,
+            e => panic!("invalid program: {}", e),
+        }
+    }
 
-            _ => Err(Error::InvalidOpcode(opcode, self.ip)),
+    pub fn step(&mut self) -> Result<Step, Error> {
+        match self.cpu.step() {
+            Ok(::intcode::Step::Continue) => Ok(Step::Continue),
+            Ok(::intcode::Step::NeedInput) => Ok(Step::NeedInput),
+            Ok(::intcode::Step::Output(value)) => Ok(Step::Output(value)),
+            Ok(::intcode::Step::Halt) => Ok(Step::Halt),
+            Err(e) => Err(self.error(e)),
         }
     }
 
     pub fn copy_with_input(&self, input: Option<Memory>) -> Self {
         Self {
-            memory: self.memory.to_owned(),
-            ip: self.ip,
-            input,
+            cpu: self.cpu.copy_with_input(input),
         }
     }
 
@@ -206,7 +128,7 @@ impl CPU {
                 match self.run() {
                     Ok(Step::Halt) => return Ok(()),
                     Ok(Step::NeedInput) => {
-                        self.input = Some(input_rx.recv().map_err(ErrorSpawn::Recv)?);
+                        self.set_input(Some(input_rx.recv().map_err(ErrorSpawn::Recv)?));
                     }
                     Ok(Step::Output(value)) => {
                         output_tx.send(value).map_err(ErrorSpawn::Send)?;
@@ -221,16 +143,8 @@ impl CPU {
     }
 }
 
-const SEPARATOR: char = ',';
-
 pub fn parse(data: &str) -> Vec<Memory> {
-    data.trim()
-        .split(SEPARATOR)
-        .map(|s| {
-            s.parse()
-                .unwrap_or_else(|e| panic!("cannot parse: {}, {}", s, e))
-        })
-        .collect()
+    ::intcode::parse(data)
 }
 
 #[cfg(test)]
@@ -252,7 +166,7 @@ mod tests {
             }
         }
 
-        println!("test_step ip: {}", cpu.ip);
+        println!("test_step ip: {}", cpu.ip());
 
         assert_eq!(memory.last(), Some(&0));
     }
@@ -274,9 +188,9 @@ mod tests {
             }
         }
 
-        println!("test_test_with_input ip: {}", cpu.ip);
+        println!("test_test_with_input ip: {}", cpu.ip());
 
-        assert_eq!(cpu.input, None);
+        assert_eq!(cpu.input(), None);
     }
 
     #[test]
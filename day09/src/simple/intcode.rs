@@ -1,8 +1,8 @@
-use std::collections::HashMap;
-use std::ops::{Index, IndexMut, Deref};
 use std::sync::mpsc::{Receiver, RecvError, SendError, Sender};
 use std::thread;
 
+use ::intcode::DecodeOpcodeError;
+
 #[derive(Debug)]
 pub enum OpcodeMode {
     Positional,
@@ -38,229 +38,71 @@ pub enum Step {
 }
 
 pub struct CPU {
-    memory: MemoryData,
-    ip: usize,
-    base: i128,
-    input: Option<Memory>,
-}
-
-#[derive(Clone)]
-struct MemoryData(HashMap<usize, Memory>);
-
-impl MemoryData {
-    fn new(data: Vec<Memory>) -> Self {
-        Self(data.into_iter().enumerate().collect())
-    }
+    cpu: ::intcode::CPU,
 }
 
-impl Deref for MemoryData {
-    type Target = HashMap<usize, Memory>;
-
-    fn deref(&self) -> &HashMap<usize, Memory> {
-        &self.0
-    }
-}
-
-impl Index<usize> for MemoryData {
-    type Output = Memory;
-
-    fn index(&self, idx: usize) -> &Memory {
-        match self.0.get(&idx) {
-            Some(value) => value,
-            None => &0,
-        }
-    }
-}
-
-impl IndexMut<usize> for MemoryData {
-    fn index_mut(&mut self, idx: usize) -> &mut Memory {
-        self.0.entry(idx).or_insert(0)
-    }
+// the first mode digit the cpu does not know
+fn mode_value(opcode: Opcode) -> u8 {
+    [100, 1000, 10000]
+        .iter()
+        .map(|weight| (opcode / weight % 10) as u8)
+        .find(|&mode| mode > 2)
+        .unwrap_or_default()
 }
 
 impl CPU {
     pub fn new(memory: Vec<Memory>, ip: usize, input: Option<Memory>) -> Self {
         Self {
-            memory: MemoryData::new(memory),
-            ip,
-            base: 0,
-            input,
+            cpu: ::intcode::CPU::new(memory, ip, input),
         }
     }
 
-    pub fn input(&self) -> Option<Memory> {
-        self.input
+    pub fn ip(&self) -> usize {
+        self.cpu.ip()
     }
 
-    pub fn set_input(&mut self, input: Option<Memory>) {
-        self.input = input;
+    pub fn memory(&self, address: usize) -> Memory {
+        self.cpu.memory(address)
     }
 
-    #[inline(always)]
-    fn mode(&self, opcode: Opcode, i: u8) -> Result<OpcodeMode, Error> {
-        match i {
-            1 => self.opcode_mode(opcode, (opcode / 100 % 10) as u8),
-            2 => self.opcode_mode(opcode, (opcode / 1000 % 10) as u8),
-            3 => self.opcode_mode(opcode, (opcode / 10000 % 10) as u8),
-            _ => Err(Error::InvalidOpcodeModeIndex(opcode, i)),
-        }
+    pub fn input(&self) -> Option<Memory> {
+        self.cpu.input()
     }
 
-    #[inline(always)]
-    fn opcode_mode(&self, opcode: Opcode, i: u8) -> Result<OpcodeMode, Error> {
-        match i {
-            0 => Ok(OpcodeMode::Positional),
-            1 => Ok(OpcodeMode::Immediate),
-            2 => Ok(OpcodeMode::Relative),
-            _ => Err(Error::InvalidOpcodeModeValue(opcode, i)),
-        }
+    pub fn set_input(&mut self, input: Option<Memory>) {
+        self.cpu.set_input(input);
     }
 
-    #[inline(always)]
-    fn write(
-        &mut self,
-        opcode: Opcode,
-        index: usize,
-        mode: OpcodeMode,
-        value: Memory,
-    ) -> Result<(), Error> {
-        match mode {
-            OpcodeMode::Positional => {
-                let idx = self.memory[index] as usize;
-                self.memory[idx] = value;
-                Ok(())
+    fn error(&self, error: ::intcode::Error) -> Error {
+        match error {
+            ::intcode::Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(opcode, ip)) => {
+                Error::InvalidOpcode(opcode as Opcode, ip)
             }
-            OpcodeMode::Immediate => Err(Error::InvalidOpcodeMode(opcode, mode)),
-            OpcodeMode::Relative => {
-                let idx = (self.base + self.memory[index]) as usize;
-                self.memory[idx] = value;
-                Ok(())
+            ::intcode::Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcodeModeValue(
+                opcode,
+                _,
+            )) => Error::InvalidOpcodeModeValue(opcode as Opcode, mode_value(opcode as Opcode)),
+            ::intcode::Error::InvalidOpcodeMode(_, _, ip) => {
+                Error::InvalidOpcodeMode(self.cpu.memory(ip) as Opcode, OpcodeMode::Immediate)
             }
-        }
-    }
-
-    #[inline(always)]
-    fn read(&self, _opcode: Opcode, index: usize, mode: OpcodeMode) -> Result<Memory, Error> {
-        let memory = &self.memory;
-        match mode {
-            OpcodeMode::Positional => Ok(memory[memory[index] as usize]),
-            OpcodeMode::Immediate => Ok(memory[index]),
-            OpcodeMode::Relative => Ok(memory[(memory[index] + self.base) as usize]),
+            ::intcode::Error::EOF => Error::EOF,
+            e => panic!("invalid program: {}", e),
         }
     }
 
     pub fn step(&mut self) -> Result<Step, Error> {
-        let opcode = self
-            .memory
-            .get(&self.ip)
-            .ok_or_else(|| Error::EOF)?
-            .to_owned() as Opcode;
-
-        match opcode % 100 {
-            1 => {
-                self.write(
-                    opcode,
-                    self.ip + 3,
-                    self.mode(opcode, 3)?,
-                    self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)?
-                        + self.read(opcode, self.ip + 2, self.mode(opcode, 2)?)?,
-                )?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            2 => {
-                self.write(
-                    opcode,
-                    self.ip + 3,
-                    self.mode(opcode, 3)?,
-                    self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)?
-                        * self.read(opcode, self.ip + 2, self.mode(opcode, 2)?)?,
-                )?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            3 => {
-                if let Some(input) = self.input {
-                    self.write(opcode, self.ip + 1, self.mode(opcode, 1)?, input)?;
-                    self.input = None;
-                    self.ip += 2;
-
-                    Ok(Step::Continue)
-                } else {
-                    Ok(Step::NeedInput)
-                }
-            }
-            4 => {
-                let output = self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)?;
-                self.ip += 2;
-
-                Ok(Step::Output(output))
-            }
-            5 => {
-                self.ip = if self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)? != 0 {
-                    self.read(opcode, self.ip + 2, self.mode(opcode, 2)?)? as usize
-                } else {
-                    self.ip + 3
-                };
-
-                Ok(Step::Continue)
-            }
-            6 => {
-                self.ip = if self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)? == 0 {
-                    self.read(opcode, self.ip + 2, self.mode(opcode, 2)?)? as usize
-                } else {
-                    self.ip + 3
-                };
-
-                Ok(Step::Continue)
-            }
-            7 => {
-                let value = if self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)?
-                    < self.read(opcode, self.ip + 2, self.mode(opcode, 2)?)?
-                {
-                    1
-                } else {
-                    0
-                };
-
-                self.write(opcode, self.ip + 3, self.mode(opcode, 3)?, value)?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            8 => {
-                let value = if self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)?
-                    == self.read(opcode, self.ip + 2, self.mode(opcode, 2)?)?
-                {
-                    1
-                } else {
-                    0
-                };
-
-                self.write(opcode, self.ip + 3, self.mode(opcode, 3)?, value)?;
-                self.ip += 4;
-
-                Ok(Step::Continue)
-            }
-            9 => {
-                self.base += self.read(opcode, self.ip + 1, self.mode(opcode, 1)?)?;
-                self.ip += 2;
-
-                Ok(Step::Continue)
-            }
-            99 => Ok(Step::Halt),
-
-            _ => Err(Error::InvalidOpcode(opcode, self.ip)),
+        match self.cpu.step() {
+            Ok(::intcode::Step::Continue) => Ok(Step::Continue),
+            Ok(::intcode::Step::NeedInput) => Ok(Step::NeedInput),
+            Ok(::intcode::Step::Output(value)) => Ok(Step::Output(value)),
+            Ok(::intcode::Step::Halt) => Ok(Step::Halt),
+            Err(e) => Err(self.error(e)),
         }
     }
 
     pub fn copy_with_input(&self, input: Option<Memory>) -> Self {
         Self {
-            memory: self.memory.clone(),
-            input,
-            ..*self
+            cpu: self.cpu.copy_with_input(input),
         }
     }
 
@@ -283,7 +125,7 @@ impl CPU {
                 match self.run() {
                     Ok(Step::Halt) => return Ok(()),
                     Ok(Step::NeedInput) => {
-                        self.input = Some(input_rx.recv().map_err(ErrorSpawn::Recv)?);
+                        self.set_input(Some(input_rx.recv().map_err(ErrorSpawn::Recv)?));
                     }
                     Ok(Step::Output(value)) => {
                         output_tx.send(value).map_err(ErrorSpawn::Send)?;
@@ -318,7 +160,7 @@ mod tests {
             }
         }
 
-        println!("test_step ip: {}", cpu.ip);
+        println!("test_step ip: {}", cpu.ip());
 
         assert_eq!(memory.last(), Some(&0));
     }
@@ -339,9 +181,9 @@ mod tests {
             }
         }
 
-        println!("test_test_with_input ip: {}", cpu.ip);
+        println!("test_test_with_input ip: {}", cpu.ip());
 
-        assert_eq!(cpu.input, None);
+        assert_eq!(cpu.input(), None);
     }
 
     #[test]
@@ -76,6 +76,25 @@ mod tests {
         assert_eq!(output.len(), 1);
     }
 
+    // BOOST in sensor boost mode on the shared cpu, with and without the
+    // decoded instruction cache
+    fn boost(cache: bool) -> Memory {
+        let mut cpu = ::intcode::CPU::new(DATA.to_owned(), 0, Some(2));
+        if cache {
+            cpu.enable_decode_cache();
+        }
+
+        match cpu.run() {
+            Ok(::intcode::Run::Output(value)) => value,
+            state => panic!("invalid state {:?}", state),
+        }
+    }
+
+    #[test]
+    fn test_boost_cache() {
+        assert_eq!(boost(true), boost(false));
+    }
+
     #[bench]
     fn bench_part_1(b: &mut Bencher) {
         b.iter(part_1);
@@ -85,4 +104,14 @@ mod tests {
     fn bench_part_2(b: &mut Bencher) {
         b.iter(part_2);
     }
+
+    #[bench]
+    fn bench_boost(b: &mut Bencher) {
+        b.iter(|| boost(false));
+    }
+
+    #[bench]
+    fn bench_boost_cached(b: &mut Bencher) {
+        b.iter(|| boost(true));
+    }
 }
@@ -97,15 +97,10 @@ impl Game {
     }
 
     pub fn new(istructions: &[intcode::Memory], coins: Option<intcode::Memory>) -> Self {
-        let istructions = if let Some(coins) = coins {
-            istructions
-                .iter()
-                .enumerate()
-                .map(|(index, &value)| if index == 0 { coins } else { value })
-                .collect()
-        } else {
-            istructions.to_vec()
-        };
+        let mut cpu = intcode::CPU::new(istructions.to_vec(), 0, None);
+        if let Some(coins) = coins {
+            cpu.poke(0, coins);
+        }
 
         Self {
             ball_position: None,
@@ -113,7 +108,7 @@ impl Game {
             score: None,
             output: [0, 0, 0],
             output_index: 0,
-            cpu: intcode::CPU::new(istructions, 0, None),
+            cpu,
         }
     }
 
@@ -198,8 +193,10 @@ pub fn part_1() -> usize {
 }
 
 pub fn part_2() -> intcode::Memory {
-    let mut game = Game::new(&ISTRUCTIONS, Some(2));
+    play(Game::new(&ISTRUCTIONS, Some(2)))
+}
 
+fn play(mut game: Game) -> intcode::Memory {
     while game.play() != Event::Halt {}
 
     game.score().unwrap()
@@ -208,6 +205,7 @@ pub fn part_2() -> intcode::Memory {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use intcode::transcript::Transcript;
     use test::Bencher;
 
     #[bench]
@@ -215,8 +213,40 @@ mod tests {
         b.iter(part_1);
     }
 
+    fn cached_game() -> Game {
+        let mut game = Game::new(&ISTRUCTIONS, Some(2));
+        game.cpu.enable_decode_cache();
+        game
+    }
+
+    #[test]
+    fn test_transcript() {
+        let expected = include_str!("../transcripts/part_1.txt")
+            .parse::<Transcript>()
+            .expect("invalid transcript");
+        intcode::CPU::new(ISTRUCTIONS.to_vec(), 0, None)
+            .replay(&expected)
+            .unwrap_or_else(|e| panic!("diverged at {}", e));
+
+        let mut game = Game::new(&ISTRUCTIONS, None);
+        game.cpu.start_transcript();
+        while game.step(None) != Event::Halt {}
+
+        assert_eq!(game.cpu.take_transcript(), Some(expected));
+    }
+
+    #[test]
+    fn test_decode_cache() {
+        assert_eq!(play(cached_game()), part_2());
+    }
+
     #[bench]
     fn bench_part_2(b: &mut Bencher) {
         b.iter(part_2);
     }
+
+    #[bench]
+    fn bench_part_2_cached(b: &mut Bencher) {
+        b.iter(|| play(cached_game()));
+    }
 }
@@ -92,7 +92,7 @@ impl Search {
     pub fn step(&mut self) -> Result<Step, Error> {
         if self.found.is_some() {
             let (position, moves, cpu) = self.found.as_ref().unwrap();
-            Ok(Step::Found(*position, moves.to_owned(), cpu.to_owned()))
+            Ok(Step::Found(*position, moves.to_owned(), cpu.fork()))
         } else if let Some((cpu, current_position, moves)) = self.queue.pop_front() {
             self.visited.insert(current_position);
 
@@ -131,7 +131,7 @@ impl Search {
             match r {
                 Err(Ok((position, moves))) => {
                     let moves = moves.into_iter().rev().collect::<Vec<_>>();
-                    self.found = Some((position, moves.to_owned(), cpu.to_owned()));
+                    self.found = Some((position, moves.to_owned(), cpu.fork()));
                     Ok(Step::Found(position, moves, cpu))
                 }
                 Ok((discovered, next)) => Ok({
@@ -5,23 +5,19 @@ extern crate test;
 extern crate lazy_static;
 
 use intcode;
+use intcode::ascii::{Status, Terminal};
 
 mod path;
 
 lazy_static! {
     static ref PROGRAM: Vec<intcode::Memory> = intcode::parse(include_str!("../data.txt"));
     static ref OUTPUT: String = {
-        let mut cpu = intcode::CPU::new(PROGRAM.to_vec(), 0, None);
-        let mut output: Vec<char> = vec![];
-        loop {
-            match cpu.run().expect("invalid cpu run state") {
-                intcode::Run::Halt => break,
-                intcode::Run::NeedInput => unreachable!(),
-                intcode::Run::Output(value) => output.push(char::from(value as u8)),
-            }
-        }
+        let screen = Terminal::new(intcode::CPU::new(PROGRAM.to_vec(), 0, None))
+            .read()
+            .expect("invalid cpu run state");
+        assert_eq!(screen.status, Status::Halt);
 
-        output.iter().collect()
+        screen.text
     };
 }
 
@@ -112,11 +108,7 @@ fn solve_2(map: &str) -> intcode::Memory {
         + &encode(&c).unwrap()
         + "\nn\n";
 
-    let mut input = tmp.chars();
-
-    let mut output = vec![];
-
-    let mut cpu = intcode::CPU::new(
+    let mut terminal = Terminal::new(intcode::CPU::new(
         PROGRAM
             .iter()
             .enumerate()
@@ -124,22 +116,15 @@ fn solve_2(map: &str) -> intcode::Memory {
             .collect::<Vec<_>>(),
         0,
         None,
-    );
-    loop {
-        match cpu.run().expect("invalid state") {
-            intcode::Run::NeedInput => {
-                if let Some(c) = input.next() {
-                    cpu.set_input(Some(c as u8 as intcode::Memory));
-                } else {
-                    panic!("EOF");
-                }
-            }
-            intcode::Run::Output(value) => output.push(value),
-            intcode::Run::Halt => break,
-        }
+    ));
+    terminal.send(&tmp).expect("invalid input");
+
+    let screen = terminal.read().expect("invalid state");
+    if screen.status == Status::NeedInput {
+        panic!("EOF");
     }
 
-    output.last().unwrap().to_owned()
+    screen.values.last().unwrap().to_owned()
 }
 
 pub fn part_1() -> i32 {
@@ -0,0 +1,15 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use intcode::{translate, Memory};
+
+// translates the puzzle input into a native module
+fn main() {
+    let program: Vec<Memory> =
+        intcode::parse(&fs::read_to_string("data.txt").expect("cannot read program"));
+    let path = Path::new(&env::var("OUT_DIR").expect("no OUT_DIR")).join("program.rs");
+
+    fs::write(path, translate::translate(&program)).expect("cannot write program");
+    println!("cargo:rerun-if-changed=data.txt");
+}
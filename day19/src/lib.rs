@@ -5,34 +5,53 @@ extern crate test;
 extern crate lazy_static;
 
 use intcode;
+use intcode::device::{Collector, Feeder};
+
+use intcode::translate::Machine;
+
+mod program {
+    include!(concat!(env!("OUT_DIR"), "/program.rs"));
+}
 
 lazy_static! {
-    static ref PROGRAM: Vec<intcode::Memory> = intcode::parse(include_str!("../data.txt"));
+    static ref MACHINE: Machine = program::machine();
 }
 
-fn check(x: usize, y: usize) -> bool {
-    let mut cpu = intcode::CPU::new(PROGRAM.to_vec(), 0, Some(x as intcode::Memory));
+fn device(x: usize, y: usize) -> (Feeder, Collector) {
+    (
+        Feeder::new(vec![x as intcode::Memory, y as intcode::Memory]),
+        Collector::new(),
+    )
+}
 
-    loop {
-        match cpu.run().expect("got error") {
-            intcode::Run::NeedInput => {
-                cpu.set_input(Some(y as intcode::Memory));
-                match cpu.run().expect("got error") {
-                    intcode::Run::Output(value) => {
-                        break match value {
-                            0 => false,
-                            1 => true,
-                            r => panic!("invalid response: {}", r),
-                        }
-                    }
-                    _ => unreachable!(),
-                }
-            }
-            s => panic!("invalid state: {:?} as ({}, {})", s, x, y),
-        }
+fn response(state: intcode::Run, device: &(Feeder, Collector), x: usize, y: usize) -> bool {
+    match state {
+        intcode::Run::Halt => match device.1.values() {
+            [0] => false,
+            [1] => true,
+            r => panic!("invalid response: {:?}", r),
+        },
+        s => panic!("invalid state: {:?} as ({}, {})", s, x, y),
     }
 }
 
+fn check(x: usize, y: usize) -> bool {
+    let mut machine = MACHINE.fork();
+    let mut device = device(x, y);
+
+    let state = machine.run_with(&mut device).expect("got error");
+    response(state, &device, x, y)
+}
+
+#[cfg(test)]
+fn check_interpreted(x: usize, y: usize) -> bool {
+    let mut cpu = intcode::CPU::new(program::PROGRAM.to_vec(), 0, None);
+    let mut device = device(x, y);
+
+    let state = cpu.run_with(&mut device).expect("got error");
+    response(state, &device, x, y)
+}
+
 pub fn part_1() -> usize {
     (0..50)
         .map(|x| {
@@ -65,6 +84,25 @@ mod tests {
     use super::*;
     use test::Bencher;
 
+    #[test]
+    fn test_same_as_interpreter() {
+        for x in 0..50 {
+            for y in 0..50 {
+                assert_eq!(check(x, y), check_interpreted(x, y), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[bench]
+    fn bench_check(b: &mut Bencher) {
+        b.iter(|| check(30, 40));
+    }
+
+    #[bench]
+    fn bench_check_interpreted(b: &mut Bencher) {
+        b.iter(|| check_interpreted(30, 40));
+    }
+
     #[bench]
     fn bench_part_1(b: &mut Bencher) {
         b.iter(part_1);
@@ -5,77 +5,73 @@ extern crate test;
 extern crate lazy_static;
 
 use intcode;
+use intcode::ascii::{Status, Terminal};
 
 lazy_static! {
     static ref PROGRAM: Vec<intcode::Memory> = intcode::parse(include_str!("../data.txt"));
 }
 
+fn solve(mut terminal: Terminal) -> intcode::Memory {
+    let screen = terminal.read().expect("invalid program");
+    if screen.status == Status::NeedInput {
+        panic!("EOF");
+    }
+
+    match screen.values.last() {
+        Some(&value) => value,
+        None => panic!("{}", screen.text),
+    }
+}
+
 pub fn part_1() -> intcode::Memory {
-    let mut cpu = intcode::CPU::new(PROGRAM.to_vec(), 0, None);
+    let mut terminal = Terminal::new(intcode::CPU::new(PROGRAM.to_vec(), 0, None));
 
     // must jump: !A & D
     // can jump: !B & D | !C & D = (!B | !C) & D = !(B & C) & D
     // jump: !A & D | (!B | !C) & D = (!A | !B | !C)) & D = !(A & B & C) & D
-    let mut input = "OR A J\nAND B J\nAND C J\nNOT J J\nAND D J\nWALK\n".chars();
-    let mut output = vec![];
-    let mut result = None;
-    loop {
-        match cpu.run().expect("invalid program") {
-            intcode::Run::NeedInput => {
-                if let Some(c) = input.next() {
-                    cpu.set_input(Some(c as intcode::Memory));
-                } else {
-                    panic!("EOF");
-                }
-            }
-            intcode::Run::Output(value) if value < 255 => output.push(value as u8 as char),
-            intcode::Run::Output(value) => result = Some(value),
-            intcode::Run::Halt => break,
-        }
-    }
+    terminal
+        .send("OR A J\nAND B J\nAND C J\nNOT J J\nAND D J\nWALK\n")
+        .expect("invalid script");
 
-    match result {
-        Some(value) => value,
-        None => panic!("{}", output.into_iter().collect::<String>()),
-    }
+    solve(terminal)
 }
 
 pub fn part_2() -> intcode::Memory {
-    let mut cpu = intcode::CPU::new(PROGRAM.to_vec(), 0, None);
+    let mut terminal = Terminal::new(intcode::CPU::new(PROGRAM.to_vec(), 0, None));
 
     // must jump: !A & D & (!E -> H)
     // can jump: (!B & D & (!E -> H)) | (!C & D & (!E -> H)) = (!B | !C) & D & (!E -> H)
     // jump: must jump | can jump = (!A | !B | !C) & (!E -> H) & D = !(A & B & C) & (!E -> H) & D = (!A | !B | !C) & (!E -> H) & D = !(A & B & C) & (E | H) & D
-    let mut input =
-        "OR A J\nAND B J\nAND C J\nNOT J J\nAND D J\nOR E T\nOR H T\nAND T J\nRUN\n".chars();
-    let mut output = vec![];
-    let mut result = None;
-    loop {
-        match cpu.run().expect("invalid program") {
-            intcode::Run::NeedInput => {
-                if let Some(c) = input.next() {
-                    cpu.set_input(Some(c as intcode::Memory));
-                } else {
-                    panic!("EOF");
-                }
-            }
-            intcode::Run::Output(value) if value < 255 => output.push(value as u8 as char),
-            intcode::Run::Output(value) => result = Some(value),
-            intcode::Run::Halt => break,
-        }
-    }
+    terminal
+        .send("OR A J\nAND B J\nAND C J\nNOT J J\nAND D J\nOR E T\nOR H T\nAND T J\nRUN\n")
+        .expect("invalid script");
 
-    match result {
-        Some(value) => value,
-        None => panic!("{}", output.into_iter().collect::<String>()),
-    }
+    solve(terminal)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use intcode::transcript::Transcript;
     use test::Bencher;
 
+    #[test]
+    fn test_transcripts() {
+        let transcripts = [
+            (include_str!("../transcripts/part_1.txt"), part_1()),
+            (include_str!("../transcripts/part_2.txt"), part_2()),
+        ];
+
+        for (text, answer) in transcripts.iter() {
+            let expected = text.parse::<Transcript>().expect("invalid transcript");
+            intcode::CPU::new(PROGRAM.to_vec(), 0, None)
+                .replay(&expected)
+                .unwrap_or_else(|e| panic!("diverged at {}", e));
+
+            assert_eq!(expected.outputs().last(), Some(answer));
+        }
+    }
+
     #[bench]
     fn bench_part_1(b: &mut Bencher) {
         b.iter(part_1);
@@ -4,83 +4,95 @@ extern crate test;
 #[macro_use]
 extern crate lazy_static;
 
-use intcode;
-
-mod nic;
+use intcode::device::Control;
+use intcode::scheduler::{Event, Queues, Scheduler, Stop};
 
 lazy_static! {
     static ref PROGRAM: Vec<intcode::Memory> = intcode::parse(include_str!("../data.txt"));
 }
 
-pub fn part_1() -> intcode::Memory {
-    let mut cpus = (0..50)
-        .map(|i| nic::NIC::new(i, PROGRAM.to_vec()))
-        .collect::<Vec<nic::NIC>>();
+const NAT: intcode::Memory = 255;
+
+fn network() -> Scheduler {
+    let mut scheduler = Scheduler::new(1000);
+    for i in 0..50 {
+        let input = i.to_string();
+        scheduler.add(
+            intcode::CPU::new(PROGRAM.to_vec(), 0, None),
+            &input,
+            &format!("out{}", i),
+            Some(-1),
+        );
+        scheduler.queues_mut().push(&input, i as intcode::Memory);
+    }
 
-    loop {
-        let mut packets = vec![];
+    scheduler
+}
 
-        for (i, cpu) in cpus.iter_mut().enumerate() {
-            match cpu.run() {
-                nic::State::Send(nic::Packet {
-                    destination: 255,
-                    y,
-                    ..
-                }) => return y,
-                nic::State::Send(packet) => packets.push(packet),
-                nic::State::Running => {}
-                nic::State::Halted => panic!("cpu {} halted", i),
+// delivers the complete packets, the ones for the nat are returned
+fn route(queues: &mut Queues) -> Vec<(intcode::Memory, intcode::Memory)> {
+    let mut nat = vec![];
+    for i in 0..50 {
+        let output = format!("out{}", i);
+        while queues.len(&output) >= 3 {
+            let destination = queues.pop(&output).unwrap();
+            let x = queues.pop(&output).unwrap();
+            let y = queues.pop(&output).unwrap();
+            if destination == NAT {
+                nat.push((x, y));
+            } else {
+                queues.extend(&destination.to_string(), [x, y]);
             }
         }
+    }
+
+    nat
+}
+
+pub fn part_1() -> intcode::Memory {
+    let mut result = None;
+    let stop = network().run(|_, queues| match route(queues).first() {
+        Some(&(_, y)) => {
+            result = Some(y);
+            Control::Pause
+        }
+        None => Control::Continue,
+    });
 
-        packets
-            .into_iter()
-            .for_each(|packet| cpus[packet.destination as usize].send(&packet));
+    match stop {
+        Ok(Stop::Paused) => result.unwrap(),
+        stop => panic!("invalid stop {:?}", stop),
     }
 }
 
 pub fn part_2() -> intcode::Memory {
-    let mut saved_nat_packet = None;
     let mut nat_packet = None;
-
-    let mut cpus = (0..50)
-        .map(|i| nic::NIC::new(i, PROGRAM.to_vec()))
-        .collect::<Vec<nic::NIC>>();
-
-    loop {
-        let mut packets = vec![];
-
-        for (i, cpu) in cpus.iter_mut().enumerate() {
-            match cpu.run() {
-                nic::State::Send(nic::Packet {
-                    destination: 255,
-                    x,
-                    y,
-                }) => {
-                    nat_packet = Some(nic::Packet {
-                        destination: 0,
-                        x,
-                        y,
-                    })
-                }
-                nic::State::Send(packet) => packets.push(packet),
-                nic::State::Running => {}
-                nic::State::Halted => panic!("cpu {} halted", i),
+    let mut last_y = None;
+    let mut result = None;
+    let stop = network().run(|event, queues| match event {
+        Event::Round => {
+            if let Some(packet) = route(queues).pop() {
+                nat_packet = Some(packet);
             }
+            Control::Continue
         }
-
-        if packets.is_empty() && cpus.iter().all(|cpu| cpu.is_idle()) {
-            if let Some(packet) = nat_packet.take() {
-                saved_nat_packet = Some(packet.to_owned());
-                packets.push(packet);
-            } else if let Some(packet) = saved_nat_packet {
-                return packet.y;
+        Event::Quiescent => match nat_packet {
+            Some((_, y)) if last_y == Some(y) => {
+                result = Some(y);
+                Control::Pause
             }
-        }
+            Some((x, y)) => {
+                last_y = Some(y);
+                queues.extend("0", [x, y]);
+                Control::Continue
+            }
+            None => Control::Continue,
+        },
+    });
 
-        packets
-            .into_iter()
-            .for_each(|packet| cpus[packet.destination as usize].send(&packet));
+    match stop {
+        Ok(Stop::Paused) => result.unwrap(),
+        stop => panic!("invalid stop {:?}", stop),
     }
 }
 
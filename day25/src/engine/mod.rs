@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use regex::Regex;
 
 use intcode;
@@ -31,6 +33,7 @@ pub enum Command {
 
 pub enum LoadCheckpointError {
     CheckpointNotFound,
+    InvalidCheckpoint(intcode::snapshot::SnapshotError),
 }
 
 pub struct Room(String);
@@ -39,25 +42,35 @@ pub struct Item(String);
 
 pub struct Engine {
     cpu: intcode::CPU,
+    checkpoints: BTreeMap<String, Vec<u8>>,
 }
 
 impl Engine {
     pub fn new() -> Self {
         Self {
             cpu: intcode::CPU::new(PROGRAM.to_vec(), 0, None),
+            checkpoints: BTreeMap::new(),
         }
     }
 
-    pub fn save_checkpoint(&mut self, _checkpoint_name: String) {
-        todo!()
+    pub fn save_checkpoint(&mut self, checkpoint_name: String) {
+        self.checkpoints.insert(checkpoint_name, self.cpu.to_bytes());
     }
 
-    pub fn load_checkpoint(&mut self, _checkpoint_name: String) -> Result<(), LoadCheckpointError> {
-        todo!()
+    pub fn load_checkpoint(&mut self, checkpoint_name: String) -> Result<(), LoadCheckpointError> {
+        let snapshot = self
+            .checkpoints
+            .get(&checkpoint_name)
+            .ok_or(LoadCheckpointError::CheckpointNotFound)?;
+
+        self.cpu =
+            intcode::CPU::from_bytes(snapshot).map_err(LoadCheckpointError::InvalidCheckpoint)?;
+
+        Ok(())
     }
 
     pub fn list_checkpoints(&self) -> Vec<String> {
-        todo!()
+        self.checkpoints.keys().cloned().collect()
     }
 }
 
@@ -65,6 +78,25 @@ impl Engine {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_checkpoints() {
+        let mut engine = Engine::new();
+        engine.save_checkpoint("start".to_string());
+
+        assert!(matches!(engine.cpu.run(), Ok(intcode::Run::Output(_))));
+        engine.save_checkpoint("output".to_string());
+
+        assert_eq!(engine.list_checkpoints(), vec!["output", "start"]);
+
+        assert!(engine.load_checkpoint("start".to_string()).is_ok());
+        assert_eq!(engine.cpu.to_bytes(), engine.checkpoints["start"]);
+
+        assert!(matches!(
+            engine.load_checkpoint("missing".to_string()),
+            Err(LoadCheckpointError::CheckpointNotFound)
+        ));
+    }
+
     #[test]
     fn test_door_re() {
         let room = r"
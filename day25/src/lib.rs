@@ -1,6 +1,7 @@
 use lazy_static::lazy_static;
 
 use intcode;
+use intcode::ascii::{Status, Terminal};
 pub mod engine;
 
 lazy_static! {
@@ -9,7 +10,7 @@ lazy_static! {
 }
 
 pub fn part_1() -> String {
-    let mut cpu = intcode::CPU::new(PROGRAM.to_vec(), 0, None);
+    let mut terminal = Terminal::new(intcode::CPU::new(PROGRAM.to_vec(), 0, None));
 
     // ignore:
     // infinite loop
@@ -20,7 +21,7 @@ pub fn part_1() -> String {
     // bowl of rice
     // monolith
     // mug
-    let mut output = Vec::new();
+    let mut output = String::new();
     let commands = vec![
         "east",
         "take mug",
@@ -58,27 +59,26 @@ pub fn part_1() -> String {
     // ];
 
     {
-        let commands = commands.join("\n");
-        let mut i = commands.chars();
+        let mut commands = Some(commands.join("\n"));
 
         loop {
-            match cpu.run().expect("invalid program") {
-                intcode::Run::NeedInput => {
-                    if let Some(c) = i.next() {
-                        cpu.set_input(Some(c as intcode::Memory));
-                        print!("{}", c);
+            let screen = terminal.read().expect("invalid program");
+            print!("{}", screen.text);
+            output.push_str(&screen.text);
+
+            match screen.status {
+                Status::Halt => {
+                    println!("--- got halt");
+                    break;
+                }
+                _ => {
+                    if let Some(commands) = commands.take() {
+                        terminal.send(&commands).expect("invalid commands");
+                        print!("{}", commands);
                     } else {
                         break;
                     }
                 }
-                intcode::Run::Output(value) => {
-                    print!("{}", value as u8 as char);
-                    output.push(value as u8 as char);
-                }
-                intcode::Run::Halt => {
-                    println!("--- got halt");
-                    break;
-                }
             }
         }
     }
@@ -1,5 +1,4 @@
-use std::collections::VecDeque;
-use std::iter;
+use intcode::ascii::{AsciiError, Status, Terminal};
 
 use yew::{html, Component, Context, Html, NodeRef};
 
@@ -8,10 +7,9 @@ use gloo_console as console;
 use web_sys::HtmlInputElement;
 
 pub struct Model {
-    cpu: intcode::CPU,
-    state: Option<Result<intcode::Run, intcode::Error>>,
+    terminal: Terminal,
+    state: Option<Result<Status, AsciiError>>,
     output: String,
-    input: VecDeque<intcode::Memory>,
     command_ref: NodeRef,
 }
 
@@ -23,31 +21,21 @@ pub enum Msg {
 impl Model {
     fn run(&mut self) {
         console::log!("run");
-        if self.state.is_none() {
-            self.state = Some(self.cpu.run());
-        }
-
-        while let Some(ref state) = self.state {
-            match state {
-                Ok(intcode::Run::Output(c)) => {
-                    self.output.push(u8::try_from(*c).unwrap() as char);
-                    self.state = Some(self.cpu.run());
-                }
-                Ok(intcode::Run::NeedInput) => {
-                    if let Some(c) = self.input.pop_front() {
-                        self.cpu.set_input(Some(c));
-                        self.state = Some(self.cpu.run());
-                    } else {
-                        break;
-                    }
-                }
-                _ => break,
+        self.state = Some(match self.terminal.read() {
+            Ok(screen) => {
+                self.output.push_str(&screen.text);
+                Ok(screen.status)
             }
-        }
+            Err(e) => Err(e),
+        });
     }
 
-    fn init_cpu() -> intcode::CPU {
-        intcode::CPU::new(intcode::parse(include_str!("../data.txt")), 0, None)
+    fn init_terminal() -> Terminal {
+        Terminal::new(intcode::CPU::new(
+            intcode::parse(include_str!("../data.txt")),
+            0,
+            None,
+        ))
     }
 }
 
@@ -58,9 +46,8 @@ impl Component for Model {
     fn create(_: &Context<Self>) -> Self {
         console::log!("create");
         let mut r = Self {
-            cpu: Self::init_cpu(),
+            terminal: Self::init_terminal(),
             state: None,
-            input: VecDeque::new(),
             output: String::new(),
             command_ref: NodeRef::default(),
         };
@@ -71,11 +58,10 @@ impl Component for Model {
     fn update(&mut self, _: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::Start => {
-                self.cpu = Self::init_cpu();
+                self.terminal = Self::init_terminal();
                 self.state = None;
                 self.output.clear();
-                self.input.clear();
-                
+
                 self.run();
 
                 true
@@ -84,14 +70,13 @@ impl Component for Model {
                 console::log!("command", &value);
 
                 self.output.clear();
-                self.input.clear();
 
-                for c in value.chars().chain(iter::once('\n')) {
-                    self.input.push_back(c as intcode::Memory);
+                self.terminal.cpu_mut().set_input(None);
+                match self.terminal.send_line(&value) {
+                    Ok(()) => self.run(),
+                    Err(e) => self.state = Some(Err(e)),
                 }
 
-                self.run();
-
                 true
             }
         }
@@ -111,15 +96,15 @@ impl Component for Model {
         });
 
         let disabled = match self.state {
-            None | Some(Ok(intcode::Run::Halt)) => false,
+            None | Some(Ok(Status::Halt)) => false,
             _ => true,
         };
 
         let state = match self.state {
-            Some(Ok(intcode::Run::NeedInput)) => "Waiting command...",
-            Some(Ok(intcode::Run::Halt)) => "Halted!",
-            Some(Ok(intcode::Run::Output(_))) => "Output...",
-            Some(Err(_)) => "Error!",
+            Some(Ok(Status::NeedInput | Status::Prompt)) => "Waiting command...",
+            Some(Ok(Status::Halt)) => "Halted!",
+            Some(Err(AsciiError::NonAscii(_))) => "Invalid command!",
+            Some(Err(AsciiError::CPU(_))) => "Error!",
             None => "Not started!",
         };
 
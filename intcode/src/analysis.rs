@@ -0,0 +1,585 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::disasm::{decode, Line};
+use crate::{Memory, Mode, Opcode};
+
+// how control leaves a basic block
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exit {
+    // falls into the block that starts right after
+    Next,
+    Jump,
+    Branch,
+    Call,
+    Return,
+    // a jump whose target is only known at run time
+    Computed,
+    Halt,
+    // runs into a word that does not decode, or past the end
+    Invalid,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EdgeKind {
+    Next,
+    Taken,
+    NotTaken,
+    Call,
+    // from a call site to the instruction the callee returns to
+    AfterCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub start: usize,
+    pub lines: Vec<Line>,
+    pub exit: Exit,
+}
+
+impl Block {
+    // the address after the last instruction
+    pub fn end(&self) -> usize {
+        self.lines
+            .last()
+            .map_or(self.start, |line| line.address() + line.words())
+    }
+}
+
+// the blocks reachable from an entry without following calls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: usize,
+    pub blocks: Vec<usize>,
+    pub returns: Vec<usize>,
+    pub calls: Vec<usize>,
+}
+
+// a write whose address is known statically and lands on an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub site: usize,
+    pub address: usize,
+    pub instruction: usize,
+}
+
+pub struct Analysis {
+    blocks: BTreeMap<usize, Block>,
+    edges: Vec<Edge>,
+    functions: Vec<Function>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    code_writes: Vec<CodeWrite>,
+    computed: Vec<usize>,
+    invalid: Vec<usize>,
+}
+
+type Code = BTreeMap<usize, (Opcode, Vec<Memory>)>;
+
+enum Flow {
+    Continue,
+    End(Exit, Vec<(EdgeKind, usize)>),
+}
+
+// the call idiom pushes the return address at the top of the stack and then
+// jumps, that is add #next, #0, rb+k followed by an unconditional jump
+fn is_call(program: &[Memory], address: usize, next: usize) -> bool {
+    address >= 4
+        && match decode(program, address - 4) {
+            Some((Opcode::Add(Mode::Immediate, Mode::Immediate, Mode::Relative), parameters)) => {
+                parameters[0].checked_add(parameters[1]) == Some(next as Memory)
+            }
+            Some((Opcode::Mul(Mode::Immediate, Mode::Immediate, Mode::Relative), parameters)) => {
+                parameters[0].checked_mul(parameters[1]) == Some(next as Memory)
+            }
+            _ => false,
+        }
+}
+
+fn flow(program: &[Memory], address: usize, opcode: &Opcode, parameters: &[Memory]) -> Flow {
+    let next = address + parameters.len() + 1;
+    let (condition, target, taken): (_, _, fn(Memory) -> bool) = match *opcode {
+        Opcode::Halt => return Flow::End(Exit::Halt, vec![]),
+        Opcode::IfNEq(condition, target) => (condition, target, |value| value != 0),
+        Opcode::IfEq(condition, target) => (condition, target, |value| value == 0),
+        _ => return Flow::Continue,
+    };
+
+    let always = match condition {
+        Mode::Immediate => Some(taken(parameters[0])),
+        _ => None,
+    };
+    let known = match target {
+        Mode::Immediate => usize::try_from(parameters[1]).ok(),
+        _ => None,
+    };
+
+    match (always, known) {
+        (Some(false), _) => Flow::Continue,
+        (Some(true), Some(known)) if is_call(program, address, next) => Flow::End(
+            Exit::Call,
+            vec![(EdgeKind::Call, known), (EdgeKind::AfterCall, next)],
+        ),
+        (Some(true), Some(known)) => Flow::End(Exit::Jump, vec![(EdgeKind::Taken, known)]),
+        (Some(true), None) if matches!(target, Mode::Relative) => Flow::End(Exit::Return, vec![]),
+        (Some(true), None) => Flow::End(Exit::Computed, vec![]),
+        (None, Some(known)) => Flow::End(
+            Exit::Branch,
+            vec![(EdgeKind::Taken, known), (EdgeKind::NotTaken, next)],
+        ),
+        (None, None) => Flow::End(Exit::Computed, vec![(EdgeKind::NotTaken, next)]),
+    }
+}
+
+fn written(opcode: &Opcode, parameters: &[Memory]) -> Option<usize> {
+    let (mode, value) = match *opcode {
+        Opcode::Add(_, _, mode)
+        | Opcode::Mul(_, _, mode)
+        | Opcode::IfLess(_, _, mode)
+        | Opcode::IfEqTo(_, _, mode) => (mode, parameters[2]),
+        Opcode::Input(mode) => (mode, parameters[0]),
+        _ => return None,
+    };
+
+    match mode {
+        Mode::Position => usize::try_from(value).ok(),
+        _ => None,
+    }
+}
+
+// the blocks reachable from entry, a call is not followed
+fn function(entry: usize, blocks: &BTreeMap<usize, Block>, edges: &[Edge]) -> Function {
+    let mut seen = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut queue = vec![entry];
+    while let Some(start) = queue.pop() {
+        if !blocks.contains_key(&start) || !seen.insert(start) {
+            continue;
+        }
+
+        for edge in edges.iter().filter(|edge| edge.from == start) {
+            if edge.kind == EdgeKind::Call {
+                calls.insert(edge.to);
+            } else {
+                queue.push(edge.to);
+            }
+        }
+    }
+
+    Function {
+        entry,
+        returns: seen
+            .iter()
+            .copied()
+            .filter(|start| blocks[start].exit == Exit::Return)
+            .collect(),
+        blocks: seen.into_iter().collect(),
+        calls: calls.into_iter().collect(),
+    }
+}
+
+// builds the control flow graph from entry point 0, without running the
+// program: only the words reachable through statically known jumps are
+// decoded
+pub fn analyze(program: &[Memory]) -> Analysis {
+    let mut code = Code::new();
+    let mut leaders = BTreeSet::from([0]);
+    let mut invalid = BTreeSet::new();
+
+    let mut queue = vec![0];
+    while let Some(address) = queue.pop() {
+        if code.contains_key(&address) || invalid.contains(&address) {
+            continue;
+        }
+
+        let (opcode, parameters) = match decode(program, address) {
+            Some(instruction) => instruction,
+            None => {
+                invalid.insert(address);
+                continue;
+            }
+        };
+
+        match flow(program, address, &opcode, &parameters) {
+            Flow::Continue => queue.push(address + parameters.len() + 1),
+            Flow::End(_, targets) => {
+                for (_, target) in targets {
+                    leaders.insert(target);
+                    queue.push(target);
+                }
+            }
+        }
+        code.insert(address, (opcode, parameters));
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut edges = vec![];
+    for &start in leaders.iter().filter(|start| code.contains_key(start)) {
+        let mut lines = vec![];
+        let mut address = start;
+        let exit = loop {
+            let (opcode, parameters) = match code.get(&address) {
+                Some((opcode, parameters)) => (opcode, parameters),
+                None => break Exit::Invalid,
+            };
+            lines.push(Line::Instruction {
+                address,
+                opcode: *opcode,
+                parameters: parameters.to_vec(),
+            });
+
+            if let Flow::End(exit, targets) = flow(program, address, opcode, parameters) {
+                edges.extend(targets.into_iter().map(|(kind, to)| Edge {
+                    from: start,
+                    to,
+                    kind,
+                }));
+                break exit;
+            }
+
+            address += parameters.len() + 1;
+            if leaders.contains(&address) {
+                edges.push(Edge {
+                    from: start,
+                    to: address,
+                    kind: EdgeKind::Next,
+                });
+                break Exit::Next;
+            }
+        };
+
+        blocks.insert(start, Block { start, lines, exit });
+    }
+
+    let entries = edges
+        .iter()
+        .filter(|edge| edge.kind == EdgeKind::Call)
+        .map(|edge| edge.to)
+        .collect::<BTreeSet<_>>();
+    let functions = entries
+        .into_iter()
+        .map(|entry| function(entry, &blocks, &edges))
+        .collect();
+
+    let sites = |matches: fn(&Opcode) -> bool| {
+        code.iter()
+            .filter(|(_, (opcode, _))| matches(opcode))
+            .map(|(&address, _)| address)
+            .collect::<Vec<_>>()
+    };
+
+    let code_writes = code
+        .iter()
+        .filter_map(|(&site, (opcode, parameters))| {
+            let address = written(opcode, parameters)?;
+            let (&instruction, (_, operands)) = code.range(..=address).next_back()?;
+            (address <= instruction + operands.len()).then_some(CodeWrite {
+                site,
+                address,
+                instruction,
+            })
+        })
+        .collect();
+
+    let computed = blocks
+        .values()
+        .filter(|block| block.exit == Exit::Computed)
+        .filter_map(|block| block.lines.last().map(Line::address))
+        .collect();
+
+    Analysis {
+        inputs: sites(|opcode| matches!(opcode, Opcode::Input(_))),
+        outputs: sites(|opcode| matches!(opcode, Opcode::Output(_))),
+        blocks,
+        edges,
+        functions,
+        code_writes,
+        computed,
+        invalid: invalid.into_iter().collect(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Analysis {
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    pub fn inputs(&self) -> &[usize] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    pub fn code_writes(&self) -> &[CodeWrite] {
+        &self.code_writes
+    }
+
+    // the jump sites the analysis could not follow
+    pub fn computed(&self) -> &[usize] {
+        &self.computed
+    }
+
+    // reachable addresses that do not hold a valid instruction
+    pub fn invalid(&self) -> &[usize] {
+        &self.invalid
+    }
+
+    // graphviz, function entries are drawn with a double border
+    pub fn to_dot(&self) -> String {
+        let entries = self
+            .functions
+            .iter()
+            .map(|function| function.entry)
+            .collect::<BTreeSet<_>>();
+
+        let mut dot = String::new();
+        dot.push_str("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for line in &block.lines {
+                write!(
+                    label,
+                    "{:>5}: {}\\l",
+                    line.address(),
+                    escape(&line.to_string())
+                )
+                .unwrap();
+            }
+            let border = if entries.contains(&block.start) {
+                ", peripheries=2"
+            } else {
+                ""
+            };
+            writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, border).unwrap();
+        }
+        for address in &self.invalid {
+            writeln!(
+                dot,
+                "    b{} [label=\"{}: invalid\", style=dashed];",
+                address, address
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Taken => " [label=\"taken\"]",
+                EdgeKind::NotTaken => " [label=\"not taken\"]",
+                EdgeKind::Call => " [label=\"call\", style=dashed]",
+                EdgeKind::AfterCall => " [label=\"return\", style=dotted]",
+            };
+            writeln!(dot, "    b{} -> b{}{};", edge.from, edge.to, attributes).unwrap();
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::parse;
+
+    fn edge(from: usize, to: usize, kind: EdgeKind) -> Edge {
+        Edge { from, to, kind }
+    }
+
+    #[test]
+    fn test_branch() {
+        let program = assemble(
+            r"
+        in   [x]
+        jz   [x], #zero
+        out  #1
+        hlt
+zero:   out  #0
+        hlt
+x:      data 0
+",
+        )
+        .unwrap();
+
+        let analysis = analyze(&program);
+
+        assert_eq!(
+            analysis
+                .blocks()
+                .map(|block| (block.start, block.end(), block.exit))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 5, Exit::Branch),
+                (5, 8, Exit::Halt),
+                (8, 11, Exit::Halt)
+            ]
+        );
+        assert_eq!(
+            analysis.edges(),
+            &[edge(0, 8, EdgeKind::Taken), edge(0, 5, EdgeKind::NotTaken)]
+        );
+        assert_eq!(analysis.inputs(), &[0]);
+        assert_eq!(analysis.outputs(), &[5, 8]);
+        assert!(analysis.functions().is_empty());
+        assert!(analysis.code_writes().is_empty());
+    }
+
+    #[test]
+    fn test_call_return() {
+        let program = assemble(
+            r"
+        arb  #100
+        call #double
+        out  rb+1
+        call #double
+        hlt
+
+double: mul  rb+1, #2, rb+1
+        ret
+",
+        )
+        .unwrap();
+
+        let analysis = analyze(&program);
+
+        assert_eq!(
+            analysis.functions(),
+            &[Function {
+                entry: 19,
+                blocks: vec![19],
+                returns: vec![19],
+                calls: vec![],
+            }]
+        );
+        assert_eq!(
+            analysis.edges(),
+            &[
+                edge(0, 19, EdgeKind::Call),
+                edge(0, 9, EdgeKind::AfterCall),
+                edge(9, 19, EdgeKind::Call),
+                edge(9, 18, EdgeKind::AfterCall),
+            ]
+        );
+        assert_eq!(analysis.block(19).unwrap().exit, Exit::Return);
+        assert!(analysis.computed().is_empty());
+    }
+
+    #[test]
+    fn test_code_writes() {
+        // patches the operand of out before running it
+        let analysis = analyze(&parse("1101,1,1,5,104,0,99"));
+
+        assert_eq!(
+            analysis.code_writes(),
+            &[CodeWrite {
+                site: 0,
+                address: 5,
+                instruction: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_computed_jumps() {
+        // jumps wherever the input says, and conditionally to [x]
+        let program = parse("3,9,5,9,9,1106,0,10,99,0,99");
+
+        let analysis = analyze(&program);
+
+        assert_eq!(analysis.computed(), &[2]);
+        assert_eq!(
+            analysis
+                .blocks()
+                .map(|block| (block.start, block.exit))
+                .collect::<Vec<_>>(),
+            vec![(0, Exit::Computed), (5, Exit::Jump), (10, Exit::Halt)]
+        );
+
+        let analysis = analyze(&parse("1106,0,3,7,99"));
+        assert_eq!(analysis.invalid(), &[3]);
+        assert_eq!(analysis.block(0).unwrap().exit, Exit::Jump);
+    }
+
+    #[test]
+    fn test_huge_immediates() {
+        // neither push is a return address, and the jump target does not fit
+        let analysis = analyze(&[21101, Memory::MAX, 1, 0, 1105, 1, 0]);
+        assert_eq!(analysis.block(0).unwrap().exit, Exit::Jump);
+
+        let analysis = analyze(&[21102, Memory::MAX, 2, 0, 1105, 1, 0]);
+        assert_eq!(analysis.block(0).unwrap().exit, Exit::Jump);
+
+        let analysis = analyze(&[1105, 1, Memory::MAX]);
+        assert_eq!(analysis.block(0).unwrap().exit, Exit::Computed);
+    }
+
+    #[test]
+    fn test_dot() {
+        let analysis = analyze(&parse("3,7,1005,7,6,99,4,7,99"));
+
+        assert_eq!(
+            analysis.to_dot(),
+            r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    b0 [label="    0: in  [7]\l    2: jnz [7], #6\l"];
+    b5 [label="    5: hlt\l"];
+    b6 [label="    6: out [7]\l    8: hlt\l"];
+    b0 -> b6 [label="taken"];
+    b0 -> b5 [label="not taken"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_days() {
+        for data in [
+            include_str!("../../day09/data.txt"),
+            include_str!("../../day13/data.txt"),
+            include_str!("../../day21/data.txt"),
+        ] {
+            let analysis = analyze(&parse(data));
+
+            assert!(!analysis.inputs().is_empty());
+            assert!(!analysis.outputs().is_empty());
+            assert!(!analysis.functions().is_empty());
+            assert!(analysis
+                .functions()
+                .iter()
+                .all(|function| analysis.block(function.entry).is_some()));
+            assert!(analysis.invalid().is_empty());
+            assert!(analysis.to_dot().starts_with("digraph cfg {\n"));
+        }
+
+        // day 7 jumps through a table indexed by the phase setting
+        let analysis = analyze(&parse(include_str!("../../day07/data.txt")));
+        assert_eq!(analysis.blocks().count(), 1);
+        assert_eq!(analysis.computed().len(), 1);
+    }
+}
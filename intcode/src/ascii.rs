@@ -0,0 +1,223 @@
+use std::error;
+use std::fmt;
+
+use crate::{Error, Memory, Run, CPU};
+
+const NEWLINE: Memory = b'\n' as Memory;
+
+#[derive(Debug)]
+pub enum AsciiError {
+    NonAscii(char),
+    CPU(Error),
+}
+
+impl fmt::Display for AsciiError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            AsciiError::NonAscii(c) => write!(fmt, "non ascii character {:?}", c),
+            AsciiError::CPU(e) => write!(fmt, "cpu error: {}", e),
+        }
+    }
+}
+
+impl error::Error for AsciiError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            AsciiError::CPU(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Prompt,
+    NeedInput,
+    Halt,
+}
+
+// text printed by the program, with the values that are not ascii kept apart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screen {
+    pub text: String,
+    pub values: Vec<Memory>,
+    pub status: Status,
+}
+
+pub struct Terminal {
+    cpu: CPU,
+}
+
+impl Terminal {
+    pub fn new(cpu: CPU) -> Self {
+        Self { cpu }
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn into_cpu(self) -> CPU {
+        self.cpu
+    }
+
+    // queues the text as is, nothing is queued if it is not all ascii
+    pub fn send(&mut self, text: &str) -> Result<(), AsciiError> {
+        if let Some(c) = text.chars().find(|c| !c.is_ascii()) {
+            return Err(AsciiError::NonAscii(c));
+        }
+
+        self.cpu.extend_input(text.bytes().map(Memory::from));
+
+        Ok(())
+    }
+
+    pub fn send_line(&mut self, line: &str) -> Result<(), AsciiError> {
+        self.send(line)?;
+        self.cpu.push_input(NEWLINE);
+
+        Ok(())
+    }
+
+    // runs until the program needs more input or halts
+    pub fn read(&mut self) -> Result<Screen, AsciiError> {
+        self.run(None)
+    }
+
+    // as read, also stopping right after the program prints the prompt
+    pub fn read_until(&mut self, prompt: &str) -> Result<Screen, AsciiError> {
+        self.run(Some(prompt))
+    }
+
+    fn run(&mut self, prompt: Option<&str>) -> Result<Screen, AsciiError> {
+        let mut text = String::new();
+        let mut values = vec![];
+
+        let status = loop {
+            match self.cpu.run().map_err(AsciiError::CPU)? {
+                Run::Output(value) => match u8::try_from(value) {
+                    Ok(c) if c.is_ascii() => {
+                        text.push(char::from(c));
+                        if prompt.is_some_and(|prompt| text.ends_with(prompt)) {
+                            break Status::Prompt;
+                        }
+                    }
+                    _ => values.push(value),
+                },
+                Run::NeedInput => break Status::NeedInput,
+                Run::Halt => break Status::Halt,
+            }
+        };
+
+        Ok(Screen {
+            text,
+            values,
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    // echoes each line read prefixed by "> ", then the line length, forever
+    const ECHO: &str = r"
+line:   add  #0, #0, [count]
+        out  #62
+        out  #32
+next:   in   [c]
+        out  [c]
+        eq   [c], #10, [flag]
+        jnz  [flag], #done
+        add  [count], #1000, [count]
+        jmp  #next
+done:   out  [count]
+        jmp  #line
+c:      data 0
+count:  data 0
+flag:   data 0
+";
+
+    fn terminal() -> Terminal {
+        Terminal::new(CPU::new(assemble(ECHO).unwrap(), 0, None))
+    }
+
+    #[test]
+    fn test_read() {
+        let mut terminal = terminal();
+
+        let screen = terminal.read().unwrap();
+        assert_eq!(screen.text, "> ");
+        assert!(screen.values.is_empty());
+        assert_eq!(screen.status, Status::NeedInput);
+
+        terminal.send_line("hi").unwrap();
+        terminal.send_line("abc").unwrap();
+
+        let screen = terminal.read().unwrap();
+        assert_eq!(screen.text, "hi\n> abc\n> ");
+        assert_eq!(screen.values, vec![2000, 3000]);
+        assert_eq!(screen.status, Status::NeedInput);
+    }
+
+    #[test]
+    fn test_read_until() {
+        let mut terminal = terminal();
+        terminal.send("ab\ncd").unwrap();
+
+        let screen = terminal.read_until("> ").unwrap();
+        assert_eq!(screen.text, "> ");
+        assert_eq!(screen.status, Status::Prompt);
+
+        let screen = terminal.read_until("> ").unwrap();
+        assert_eq!(screen.text, "ab\n> ");
+        assert_eq!(screen.values, vec![2000]);
+        assert_eq!(screen.status, Status::Prompt);
+
+        let screen = terminal.read_until("> ").unwrap();
+        assert_eq!(screen.text, "cd");
+        assert_eq!(screen.status, Status::NeedInput);
+    }
+
+    #[test]
+    fn test_halt() {
+        let mut terminal = Terminal::new(CPU::new(
+            assemble("out #79\nout #75\nout #10\nout #1000\nhlt").unwrap(),
+            0,
+            None,
+        ));
+
+        let screen = terminal.read_until("Command?").unwrap();
+        assert_eq!(screen.text, "OK\n");
+        assert_eq!(screen.values, vec![1000]);
+        assert_eq!(screen.status, Status::Halt);
+    }
+
+    #[test]
+    fn test_non_ascii_input() {
+        let mut terminal = terminal();
+
+        assert!(matches!(
+            terminal.send_line("caffè"),
+            Err(AsciiError::NonAscii('è'))
+        ));
+        assert_eq!(terminal.cpu().pending_input(), 0);
+    }
+
+    #[test]
+    fn test_cpu_error() {
+        let mut terminal = Terminal::new(CPU::new(vec![42], 0, None));
+
+        assert!(matches!(terminal.read(), Err(AsciiError::CPU(_))));
+
+        let error: Box<dyn error::Error> = Box::new(terminal.read().unwrap_err());
+        assert!(error.to_string().starts_with("cpu error: "));
+        assert!(error.source().is_some());
+    }
+}
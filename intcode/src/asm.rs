@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use crate::disasm::MNEMONICS;
+use crate::{Memory, Mode};
+
+#[derive(Debug)]
+pub enum AsmError {
+    InvalidMnemonic(usize, String),
+    InvalidOperand(usize, String),
+    InvalidOperandCount(usize, usize),
+    InvalidSymbol(usize, String),
+    ImmediateWrite(usize, String),
+    UndefinedSymbol(usize, String),
+    DuplicateSymbol(usize, String),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            AsmError::InvalidMnemonic(line, token) => {
+                write!(fmt, "line {}: invalid mnemonic {:?}", line, token)
+            }
+            AsmError::InvalidOperand(line, token) => {
+                write!(fmt, "line {}: invalid operand {:?}", line, token)
+            }
+            AsmError::InvalidOperandCount(line, count) => {
+                write!(fmt, "line {}: invalid operand count {}", line, count)
+            }
+            AsmError::InvalidSymbol(line, token) => {
+                write!(fmt, "line {}: invalid symbol {:?}", line, token)
+            }
+            AsmError::ImmediateWrite(line, token) => {
+                write!(fmt, "line {}: {} writes to an immediate", line, token)
+            }
+            AsmError::UndefinedSymbol(line, token) => {
+                write!(fmt, "line {}: undefined symbol {:?}", line, token)
+            }
+            AsmError::DuplicateSymbol(line, token) => {
+                write!(fmt, "line {}: duplicate symbol {:?}", line, token)
+            }
+        }
+    }
+}
+
+impl error::Error for AsmError {}
+
+enum Item {
+    Instruction(Memory, Vec<(Mode, String)>),
+    Data(Vec<String>),
+}
+
+struct Statement {
+    line: usize,
+    item: Item,
+}
+
+// pseudo instructions built on top of the relative base: call stores the
+// return address at rb+0 before jumping, ret jumps back to it
+const CALL: &str = "call";
+const RET: &str = "ret";
+const JMP: &str = "jmp";
+// the address following the source line, that is the return address of call
+const NEXT: &str = "$next";
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn write_parameter(code: Memory) -> Option<usize> {
+    match code {
+        1 | 2 | 7 | 8 => Some(2),
+        3 => Some(0),
+        _ => None,
+    }
+}
+
+fn parse_operand(line: usize, operand: &str) -> Result<(Mode, String), AsmError> {
+    let operand = operand.trim();
+    let (mode, value) = if let Some(value) = operand
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+    {
+        (Mode::Position, value.trim().to_string())
+    } else if let Some(value) = operand.strip_prefix('#') {
+        (Mode::Immediate, value.trim().to_string())
+    } else if let Some(value) = operand.strip_prefix("rb+") {
+        (Mode::Relative, value.trim().to_string())
+    } else if let Some(value) = operand.strip_prefix("rb-") {
+        (Mode::Relative, format!("-{}", value.trim()))
+    } else if operand == "rb" {
+        (Mode::Relative, "0".to_string())
+    } else {
+        return Err(AsmError::InvalidOperand(line, operand.to_string()));
+    };
+
+    if value.is_empty() {
+        Err(AsmError::InvalidOperand(line, operand.to_string()))
+    } else {
+        Ok((mode, value))
+    }
+}
+
+fn split_operands(operands: &str) -> Vec<&str> {
+    operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+        .collect()
+}
+
+fn evaluate(
+    line: usize,
+    expression: &str,
+    symbols: &HashMap<String, Memory>,
+) -> Result<Memory, AsmError> {
+    let expression = expression.trim();
+    let value = |term: &str| -> Result<Memory, AsmError> {
+        let term = term.trim();
+        if let Ok(value) = term.parse() {
+            Ok(value)
+        } else if is_symbol(term) {
+            symbols
+                .get(term)
+                .copied()
+                .ok_or_else(|| AsmError::UndefinedSymbol(line, term.to_string()))
+        } else {
+            Err(AsmError::InvalidOperand(line, expression.to_string()))
+        }
+    };
+
+    // a single term, optionally followed by one +/- offset: label+1, n-2
+    match expression
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '+' || *c == '-')
+    {
+        Some((index, sign)) => {
+            let offset = value(&expression[index + 1..])?;
+            let base = value(&expression[..index])?;
+            if sign == '+' {
+                base.checked_add(offset)
+            } else {
+                base.checked_sub(offset)
+            }
+            .ok_or_else(|| AsmError::InvalidOperand(line, expression.to_string()))
+        }
+        None => value(expression),
+    }
+}
+
+fn expand(line: usize, name: &str, operands: &[&str]) -> Result<Vec<Item>, AsmError> {
+    let count = |expected: usize| {
+        if operands.len() == expected {
+            Ok(())
+        } else {
+            Err(AsmError::InvalidOperandCount(line, operands.len()))
+        }
+    };
+
+    match name {
+        JMP => {
+            count(1)?;
+            Ok(vec![Item::Instruction(
+                6,
+                vec![
+                    (Mode::Immediate, "0".to_string()),
+                    parse_operand(line, operands[0])?,
+                ],
+            )])
+        }
+        CALL => {
+            count(1)?;
+            Ok(vec![
+                Item::Instruction(
+                    1,
+                    vec![
+                        (Mode::Immediate, NEXT.to_string()),
+                        (Mode::Immediate, "0".to_string()),
+                        (Mode::Relative, "0".to_string()),
+                    ],
+                ),
+                Item::Instruction(
+                    6,
+                    vec![
+                        (Mode::Immediate, "0".to_string()),
+                        parse_operand(line, operands[0])?,
+                    ],
+                ),
+            ])
+        }
+        RET => {
+            count(0)?;
+            Ok(vec![Item::Instruction(
+                6,
+                vec![
+                    (Mode::Immediate, "0".to_string()),
+                    (Mode::Relative, "0".to_string()),
+                ],
+            )])
+        }
+        _ => {
+            let (_, code, arity) = MNEMONICS
+                .iter()
+                .find(|(mnemonic, _, _)| *mnemonic == name)
+                .ok_or_else(|| AsmError::InvalidMnemonic(line, name.to_string()))?;
+            count(*arity)?;
+
+            let operands = operands
+                .iter()
+                .map(|operand| parse_operand(line, operand))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(index) = write_parameter(*code) {
+                if let Mode::Immediate = operands[index].0 {
+                    return Err(AsmError::ImmediateWrite(line, name.to_string()));
+                }
+            }
+
+            Ok(vec![Item::Instruction(*code, operands)])
+        }
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Vec<Memory>, AsmError> {
+    let mut symbols = HashMap::new();
+    let mut constants = vec![];
+    let mut statements = vec![];
+    let mut ends = HashMap::new();
+    let mut address = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+
+        if let Some(definition) = text.strip_prefix("const ") {
+            let (name, value) = definition
+                .split_once('=')
+                .ok_or_else(|| AsmError::InvalidSymbol(line, definition.to_string()))?;
+            let name = name.trim();
+            if !is_symbol(name) {
+                return Err(AsmError::InvalidSymbol(line, name.to_string()));
+            }
+            constants.push((line, name.to_string(), value.trim().to_string()));
+            continue;
+        }
+
+        while let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_symbol(label) {
+                return Err(AsmError::InvalidSymbol(line, label.to_string()));
+            }
+            if symbols
+                .insert(label.to_string(), address as Memory)
+                .is_some()
+            {
+                return Err(AsmError::DuplicateSymbol(line, label.to_string()));
+            }
+            text = rest.trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let (name, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let operands = split_operands(operands);
+
+        let items = if name == "data" {
+            vec![Item::Data(
+                operands.into_iter().map(str::to_string).collect(),
+            )]
+        } else {
+            expand(line, name, &operands)?
+        };
+
+        for item in items {
+            let size = match &item {
+                Item::Instruction(_, operands) => operands.len() + 1,
+                Item::Data(values) => values.len(),
+            };
+            statements.push(Statement { line, item });
+            address += size;
+        }
+        ends.insert(line, address);
+    }
+
+    for (line, name, value) in constants {
+        let value = evaluate(line, &value, &symbols)?;
+        if symbols.insert(name.to_owned(), value).is_some() {
+            return Err(AsmError::DuplicateSymbol(line, name));
+        }
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for Statement { line, item } in statements {
+        match item {
+            Item::Instruction(code, operands) => {
+                let next = ends[&line] as Memory;
+                program.push(
+                    operands
+                        .iter()
+                        .zip([100, 1000, 10000])
+                        .fold(code, |acc, ((mode, _), weight)| acc + mode.code() * weight),
+                );
+                for (_, value) in operands {
+                    program.push(if value == NEXT {
+                        next
+                    } else {
+                        evaluate(line, &value, &symbols)?
+                    });
+                }
+            }
+            Item::Data(values) => {
+                for value in values {
+                    program.push(evaluate(line, &value, &symbols)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Run, CPU};
+
+    #[test]
+    fn test_input_program() {
+        let program = assemble(
+            r"
+; reads a and b, outputs a + 10 * b
+        in   [a]
+        in   [b]
+        mul  [b], #10, [b]
+        add  [b], [a], [a]
+        out  [a]
+        hlt
+a:      data 0
+b:      data 0
+",
+        )
+        .expect("invalid program");
+
+        assert_eq!(
+            program,
+            parse::<Memory>(r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#)
+        );
+    }
+
+    #[test]
+    fn test_self_copy_program() {
+        let program = assemble(
+            r"
+const counter = 100
+const flag = counter + 1
+const length = 16
+
+start:  arb  #1
+        out  rb-1
+        add  [counter], #1, [counter]
+        eq   [counter], #length, [flag]
+        jz   [flag], #start
+        hlt
+",
+        )
+        .expect("invalid program");
+
+        assert_eq!(
+            program,
+            parse::<Memory>(r#"109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"#)
+        );
+    }
+
+    #[test]
+    #[allow(clippy::unreadable_literal)]
+    fn test_immediate_programs() {
+        assert_eq!(
+            assemble("mul #34915192, #34915192, [result]\nout [result]\nhlt\nresult: data 0")
+                .unwrap(),
+            parse::<Memory>(r#"1102,34915192,34915192,7,4,7,99,0"#)
+        );
+        assert_eq!(
+            assemble("out #1125899906842624\nhlt").unwrap(),
+            parse::<Memory>(r#"104,1125899906842624,99"#)
+        );
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        let program = assemble(
+            r"
+        jmp  #end
+table:  data 1, 2, table, end-1
+end:    hlt
+",
+        )
+        .unwrap();
+
+        assert_eq!(program, vec![1106, 0, 7, 1, 2, 3, 6, 99]);
+    }
+
+    #[test]
+    fn test_call_ret() {
+        let program = assemble(
+            r"
+        arb  #100
+        call #double
+        out  rb+1
+        hlt
+
+double: mul  rb+1, #2, rb+1
+        ret
+",
+        )
+        .unwrap();
+
+        let mut cpu = CPU::new(program, 0, None);
+        cpu.memory[101] = 21;
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(42))));
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            assemble("hlt\nadd #1, #2, #3"),
+            Err(AsmError::ImmediateWrite(2, _))
+        ));
+        assert!(matches!(
+            assemble("in #1"),
+            Err(AsmError::ImmediateWrite(1, _))
+        ));
+        assert!(matches!(
+            assemble("\n\nnop"),
+            Err(AsmError::InvalidMnemonic(3, _))
+        ));
+        assert!(matches!(
+            assemble("out [1], [2]"),
+            Err(AsmError::InvalidOperandCount(1, 2))
+        ));
+        assert!(matches!(
+            assemble("out 1"),
+            Err(AsmError::InvalidOperand(1, _))
+        ));
+        assert!(matches!(
+            assemble("jmp missing"),
+            Err(AsmError::InvalidOperand(1, _))
+        ));
+        assert!(matches!(
+            assemble("jmp #missing"),
+            Err(AsmError::UndefinedSymbol(1, _))
+        ));
+        assert!(matches!(
+            assemble("a: hlt\na: hlt"),
+            Err(AsmError::DuplicateSymbol(2, _))
+        ));
+        assert!(matches!(
+            assemble("1a: hlt"),
+            Err(AsmError::InvalidSymbol(1, _))
+        ));
+        assert_eq!(
+            assemble("jmp #missing").unwrap_err().to_string(),
+            "line 1: undefined symbol \"missing\""
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert!(matches!(
+            assemble(&format!("hlt\nx: data x+{}", Memory::MAX)),
+            Err(AsmError::InvalidOperand(2, _))
+        ));
+        assert!(matches!(
+            assemble(&format!("out #-2-{}", Memory::MAX)),
+            Err(AsmError::InvalidOperand(1, _))
+        ));
+        assert_eq!(
+            assemble(&format!("hlt\nx: data x+{}", Memory::MAX - 1)).unwrap(),
+            vec![99, Memory::MAX]
+        );
+    }
+}
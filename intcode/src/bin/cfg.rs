@@ -0,0 +1,12 @@
+use std::env;
+use std::fs;
+
+use intcode::{analysis, Memory};
+
+fn main() {
+    let path = env::args().nth(1).expect("usage: cfg <program>");
+    let program: Vec<Memory> =
+        intcode::parse(&fs::read_to_string(path).expect("cannot read program"));
+
+    print!("{}", analysis::analyze(&program).to_dot());
+}
@@ -0,0 +1,222 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use intcode::debugger::{Debugger, Event};
+use intcode::disasm;
+use intcode::{Memory, CPU};
+
+const HELP: &str = r"commands:
+  b <addr> [if <operand> <cmp> <value>]  set a breakpoint, operand: ip, rb, [addr], rb+offset
+  d <addr>                               delete a breakpoint
+  w <addr>                               watch writes to a memory cell
+  uw <addr>                              delete a watchpoint
+  info                                   list breakpoints and watchpoints
+  s                                      step one instruction
+  n                                      step over the current instruction
+  c                                      continue
+  o                                      run until the next output
+  i <value> ...                          queue input values
+  a <text>                               queue a line of ascii input
+  r                                      show registers
+  x <addr> [count]                       examine memory
+  l [addr] [count]                       list instructions
+  p on                                   start profiling
+  p [json|listing]                       show the profile, as text, json or annotated listing
+  q                                      quit";
+
+fn address(token: Option<&str>) -> Result<usize, String> {
+    token
+        .ok_or_else(|| "missing address".to_string())?
+        .parse()
+        .map_err(|_| "invalid address".to_string())
+}
+
+fn count(token: Option<&str>, default: usize) -> Result<usize, String> {
+    token.map_or(Ok(default), |token| {
+        token.parse().map_err(|_| "invalid count".to_string())
+    })
+}
+
+fn print_output(debugger: &mut Debugger, ascii: bool) {
+    let output = debugger.take_output();
+    if output.is_empty() {
+        return;
+    }
+
+    if ascii {
+        for value in output {
+            match value {
+                0..=127 => print!("{}", value as u8 as char),
+                _ => println!("[{}]", value),
+            }
+        }
+    } else {
+        for value in output {
+            println!("output: {}", value);
+        }
+    }
+}
+
+fn print_event(debugger: &Debugger, event: Event) {
+    match event {
+        Event::Step | Event::Output(_) => {}
+        Event::Breakpoint(address) => println!("breakpoint at {}", address),
+        Event::Watchpoint { address, old, new } => {
+            println!("watchpoint [{}]: {} -> {}", address, old, new)
+        }
+        Event::NeedInput => println!("waiting for input"),
+        Event::Halt => println!("halted"),
+    }
+    println!(
+        "{:>5}: {}",
+        debugger.ip(),
+        debugger.instruction(debugger.ip())
+    );
+}
+
+fn execute(debugger: &mut Debugger, program: &[Memory], line: &str) -> Result<bool, String> {
+    let mut tokens = line.split_whitespace();
+    let command = match tokens.next() {
+        Some(command) => command,
+        None => return Ok(true),
+    };
+
+    let run = |result: Result<Event, intcode::Error>| result.map_err(|e| e.to_string());
+
+    match command {
+        "b" => {
+            let address = address(tokens.next())?;
+            let condition = match tokens.next() {
+                Some("if") => Some(tokens.collect::<Vec<_>>().join(" ").parse()?),
+                Some(_) => return Err("expected: b <addr> if <condition>".to_string()),
+                None => None,
+            };
+            debugger.add_breakpoint(address, condition);
+        }
+        "d" => {
+            if !debugger.remove_breakpoint(address(tokens.next())?) {
+                return Err("no such breakpoint".to_string());
+            }
+        }
+        "w" => debugger.add_watchpoint(address(tokens.next())?),
+        "uw" => {
+            if !debugger.remove_watchpoint(address(tokens.next())?) {
+                return Err("no such watchpoint".to_string());
+            }
+        }
+        "info" => {
+            for (address, condition) in debugger.breakpoints() {
+                match condition {
+                    Some(condition) => println!("breakpoint {} if {:?}", address, condition),
+                    None => println!("breakpoint {}", address),
+                }
+            }
+            for address in debugger.watchpoints() {
+                println!("watchpoint {}", address);
+            }
+        }
+        "s" => {
+            let event = run(debugger.step())?;
+            print_event(debugger, event);
+        }
+        "n" => {
+            let event = run(debugger.step_over())?;
+            print_event(debugger, event);
+        }
+        "c" => {
+            let event = run(debugger.cont())?;
+            print_event(debugger, event);
+        }
+        "o" => {
+            let event = run(debugger.run_until_output())?;
+            print_event(debugger, event);
+        }
+        "i" => {
+            for token in tokens {
+                debugger.push_input(
+                    token
+                        .parse::<Memory>()
+                        .map_err(|_| format!("invalid input: {}", token))?,
+                );
+            }
+        }
+        "a" => {
+            let text = line.trim_start()[1..].trim_start();
+            for c in text.chars().chain(Some('\n')) {
+                debugger.push_input(c as Memory);
+            }
+        }
+        "r" => println!("ip: {}, rb: {}", debugger.ip(), debugger.base()),
+        "x" => {
+            let start = address(tokens.next())?;
+            let count = count(tokens.next(), 1)?;
+            for address in start..start + count {
+                println!("[{}] = {}", address, debugger.memory(address));
+            }
+        }
+        "l" => {
+            let mut address = count(tokens.next(), debugger.ip())?;
+            for _ in 0..count(tokens.next(), 10)? {
+                let instruction = debugger.instruction(address);
+                println!("{:>5}: {}", address, instruction);
+                address += instruction.words();
+            }
+        }
+        "p" => match (tokens.next(), debugger.cpu().profile()) {
+            (Some("on"), _) => debugger.cpu_mut().enable_profile(),
+            (_, None) => return Err("profiling is off".to_string()),
+            (None, Some(profile)) => print!("{}", profile),
+            (Some("json"), Some(profile)) => println!("{}", profile.to_json()),
+            (Some("listing"), Some(profile)) => {
+                print!("{}", profile.annotate(&disasm::disassemble(program)))
+            }
+            (Some(option), _) => return Err(format!("unknown profile option: {}", option)),
+        },
+        "h" => println!("{}", HELP),
+        "q" => return Ok(false),
+        _ => return Err(format!("unknown command: {}", command)),
+    }
+
+    Ok(true)
+}
+
+fn main() {
+    let mut path = None;
+    let mut ascii = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--ascii" => ascii = true,
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.expect("usage: debugger [--ascii] <program>");
+    let program: Vec<Memory> =
+        intcode::parse(&fs::read_to_string(path).expect("cannot read program"));
+    let mut debugger = Debugger::new(CPU::new(program.to_owned(), 0, None));
+
+    let stdin = io::stdin();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().expect("cannot flush stdout");
+
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .expect("cannot read stdin")
+            == 0
+        {
+            break;
+        }
+
+        let result = execute(&mut debugger, &program, &line);
+        print_output(&mut debugger, ascii);
+        match result {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
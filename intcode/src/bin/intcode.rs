@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, StdinLock, StdoutLock, Write};
+use std::process::ExitCode;
+
+use intcode::ascii::AsciiError;
+use intcode::budget::Budget;
+use intcode::patch::Patch;
+use intcode::program;
+use intcode::transcript::Transcript;
+use intcode::{Error, Memory, Run, Step, CPU};
+
+const USAGE: &str = r"usage: intcode [options] <program>
+  --ascii            read lines of text and print ascii output
+  --input <values>   comma separated values read before stdin
+  --patch <patch>    write addr=value[,value...] over the program
+  --budget <count>   stop after count instructions
+  --trace            print each instruction to stderr
+  --record <file>    write a transcript of the i/o
+  --replay <file>    run the program against a transcript instead of stdin
+exit status: 0 halted or replayed, 1 program error, 2 usage, 3 out of input,
+4 out of budget, 5 replay diverged";
+
+const HALTED: u8 = 0;
+const FAILED: u8 = 1;
+const USAGE_ERROR: u8 = 2;
+const OUT_OF_INPUT: u8 = 3;
+const OUT_OF_BUDGET: u8 = 4;
+const DIVERGED: u8 = 5;
+
+struct Options {
+    path: String,
+    ascii: bool,
+    input: Vec<Memory>,
+    patch: Patch,
+    budget: Option<u64>,
+    trace: bool,
+    record: Option<String>,
+    replay: Option<String>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut path = None;
+    let mut options = Options {
+        path: String::new(),
+        ascii: false,
+        input: vec![],
+        patch: Patch::new(),
+        budget: None,
+        trace: false,
+        record: None,
+        replay: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("missing {}", name));
+        match arg.as_str() {
+            "--ascii" => options.ascii = true,
+            "--trace" => options.trace = true,
+            "--input" => options.input.extend(
+                program::parse::<Memory>(&value("input")?)
+                    .map_err(|e| format!("invalid input: {}", e))?,
+            ),
+            "--patch" => options.patch.merge(
+                &value("patch")?
+                    .parse()
+                    .map_err(|e| format!("invalid patch: {}", e))?,
+            ),
+            "--budget" => {
+                options.budget = Some(
+                    value("budget")?
+                        .parse()
+                        .map_err(|_| "invalid budget".to_string())?,
+                )
+            }
+            "--record" => options.record = Some(value("record")?),
+            "--replay" => options.replay = Some(value("replay")?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    options.path = path.ok_or_else(|| "missing program".to_string())?;
+    Ok(options)
+}
+
+// stdin and stdout as numbers, one per line, or as ascii text
+struct Stdio<'a> {
+    ascii: bool,
+    queue: VecDeque<Memory>,
+    reader: StdinLock<'a>,
+    writer: BufWriter<StdoutLock<'a>>,
+}
+
+impl Stdio<'_> {
+    fn input(&mut self) -> Result<Option<Memory>, String> {
+        while self.queue.is_empty() {
+            self.writer.flush().map_err(|e| e.to_string())?;
+
+            let mut line = String::new();
+            if self
+                .reader
+                .read_line(&mut line)
+                .map_err(|e| e.to_string())?
+                == 0
+            {
+                return Ok(None);
+            }
+
+            if self.ascii {
+                let line = line.trim_end_matches(['\r', '\n']);
+                if let Some(c) = line.chars().find(|c| !c.is_ascii()) {
+                    return Err(AsciiError::NonAscii(c).to_string());
+                }
+                self.queue.extend(line.bytes().map(Memory::from));
+                self.queue.push_back(Memory::from(b'\n'));
+            } else if !line.trim().is_empty() {
+                self.queue.push_back(
+                    line.trim()
+                        .parse()
+                        .map_err(|_| format!("invalid input {:?}", line.trim()))?,
+                );
+            }
+        }
+
+        Ok(self.queue.pop_front())
+    }
+
+    fn output(&mut self, value: Memory) -> io::Result<()> {
+        match u8::try_from(value) {
+            Ok(c) if self.ascii && c.is_ascii() => self.writer.write_all(&[c]),
+            _ => writeln!(self.writer, "{}", value),
+        }
+    }
+}
+
+// CPU::run, stepping through the budget and the trace when they are on
+fn run(cpu: &mut CPU, budget: &mut Option<u64>, trace: bool) -> Result<Budget<Run>, Error> {
+    if budget.is_none() && !trace {
+        return cpu.run().map(Budget::Done);
+    }
+
+    let mut unlimited = u64::MAX;
+    let budget = budget.as_mut().unwrap_or(&mut unlimited);
+    loop {
+        let ip = cpu.ip();
+        let line = if trace {
+            Some(cpu.instruction(ip))
+        } else {
+            None
+        };
+
+        let step = cpu.step_budget(budget)?;
+        if let Some(line) = line {
+            if !matches!(step, Budget::Done(Step::NeedInput) | Budget::Exhausted) {
+                eprintln!("{:>5}: {}", ip, line);
+            }
+        }
+
+        match step {
+            Budget::Done(Step::Continue) => {}
+            Budget::Done(Step::NeedInput) => break Ok(Budget::Done(Run::NeedInput)),
+            Budget::Done(Step::Output(value)) => break Ok(Budget::Done(Run::Output(value))),
+            Budget::Done(Step::Halt) => break Ok(Budget::Done(Run::Halt)),
+            Budget::Exhausted => break Ok(Budget::Exhausted),
+            Budget::Loop => break Ok(Budget::Loop),
+        }
+    }
+}
+
+fn execute(options: Options) -> Result<u8, String> {
+    let program = fs::read_to_string(&options.path)
+        .map_err(|e| format!("cannot read {}: {}", options.path, e))?;
+    let program = program::parse::<Memory>(&program).map_err(|e| e.to_string())?;
+
+    let mut cpu = CPU::new(program, 0, None);
+    cpu.patch(&options.patch);
+
+    if let Some(path) = &options.replay {
+        let transcript: Transcript =
+            Transcript::load(path).map_err(|e| format!("{}: {}", path, e))?;
+        return Ok(match cpu.replay(&transcript) {
+            Ok(()) => {
+                eprintln!("replayed {} entries", transcript.len());
+                HALTED
+            }
+            Err(divergence) => {
+                eprintln!("diverged at {}", divergence);
+                DIVERGED
+            }
+        });
+    }
+    if options.record.is_some() {
+        cpu.start_transcript();
+    }
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdio = Stdio {
+        ascii: options.ascii,
+        queue: options.input.into_iter().collect(),
+        reader: stdin.lock(),
+        writer: BufWriter::new(stdout.lock()),
+    };
+
+    let mut budget = options.budget;
+    let status = loop {
+        match run(&mut cpu, &mut budget, options.trace) {
+            Ok(Budget::Done(Run::Halt)) => break HALTED,
+            Ok(Budget::Done(Run::Output(value))) => {
+                stdio.output(value).map_err(|e| e.to_string())?
+            }
+            Ok(Budget::Done(Run::NeedInput)) => match stdio.input()? {
+                Some(value) => cpu.push_input(value),
+                None => {
+                    eprintln!("out of input at {}", cpu.ip());
+                    break OUT_OF_INPUT;
+                }
+            },
+            Ok(Budget::Exhausted | Budget::Loop) => {
+                eprintln!("out of budget at {}", cpu.ip());
+                break OUT_OF_BUDGET;
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                break FAILED;
+            }
+        }
+    };
+
+    stdio.writer.flush().map_err(|e| e.to_string())?;
+    if let (Some(path), Some(transcript)) = (&options.record, cpu.transcript()) {
+        transcript
+            .save(path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(status)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+
+    match execute(options) {
+        Ok(status) => ExitCode::from(status),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(FAILED)
+        }
+    }
+}
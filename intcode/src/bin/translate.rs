@@ -0,0 +1,12 @@
+use std::env;
+use std::fs;
+
+use intcode::{translate, Memory};
+
+fn main() {
+    let path = env::args().nth(1).expect("usage: translate <program>");
+    let program: Vec<Memory> =
+        intcode::parse(&fs::read_to_string(path).expect("cannot read program"));
+
+    print!("{}", translate::translate(&program));
+}
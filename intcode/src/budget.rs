@@ -0,0 +1,240 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::word::Word;
+use crate::{Error, Run, Step, CPU};
+
+#[derive(Debug)]
+pub enum Budget<T> {
+    Done(T),
+    Exhausted,
+    Loop,
+}
+
+// brent's cycle detection over (ip, base, memory hash), the memory hash is
+// updated on every write so each check is constant time
+#[derive(Debug, Clone)]
+pub(crate) struct LoopDetector<W> {
+    hash: u64,
+    saved: Option<(usize, W, u64)>,
+    steps: u64,
+    power: u64,
+}
+
+impl<W: Word> LoopDetector<W> {
+    fn new() -> Self {
+        Self {
+            hash: 0,
+            saved: None,
+            steps: 0,
+            power: 1,
+        }
+    }
+
+    pub(crate) fn write(&mut self, index: usize, old: &W, new: &W) {
+        self.hash ^= mix(index, old) ^ mix(index, new);
+    }
+
+    fn check(&mut self, ip: usize, base: &W) -> bool {
+        if let Some((saved_ip, saved_base, saved_hash)) = &self.saved {
+            if *saved_ip == ip && saved_base == base && *saved_hash == self.hash {
+                return true;
+            }
+        }
+
+        self.steps += 1;
+        if self.steps == self.power {
+            self.saved = Some((ip, base.clone(), self.hash));
+            self.steps = 0;
+            self.power *= 2;
+        }
+
+        false
+    }
+
+    // the state after any i/o may legitimately repeat
+    fn reset(&mut self) {
+        self.saved = None;
+        self.steps = 0;
+        self.power = 1;
+    }
+}
+
+fn mix<W: Word>(index: usize, value: &W) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (index, value).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<W: Word> CPU<W> {
+    pub fn enable_loop_detection(&mut self) {
+        self.loops
+            .get_or_insert_with(|| Box::new(LoopDetector::new()));
+    }
+
+    pub fn disable_loop_detection(&mut self) {
+        self.loops = None;
+    }
+
+    // a step costs one unit of budget, waiting for input costs nothing
+    pub fn step_budget(&mut self, budget: &mut u64) -> Result<Budget<Step<W>>, Error<W>> {
+        if *budget == 0 {
+            return Ok(Budget::Exhausted);
+        }
+
+        let pending = self.pending_input();
+        let step = self.step()?;
+
+        match step {
+            Step::NeedInput => return Ok(Budget::Done(step)),
+            Step::Output(_) | Step::Halt => {
+                if let Some(loops) = self.loops.as_mut() {
+                    loops.reset();
+                }
+            }
+            Step::Continue => {
+                if let Some(loops) = self.loops.as_mut() {
+                    if pending != self.input.len() {
+                        loops.reset();
+                    } else if loops.check(self.ip, &self.base) {
+                        *budget -= 1;
+                        return Ok(Budget::Loop);
+                    }
+                }
+            }
+        }
+
+        *budget -= 1;
+        Ok(Budget::Done(step))
+    }
+
+    pub fn run_with_budget(&mut self, budget: u64) -> Result<Budget<Run<W>>, Error<W>> {
+        let mut budget = budget;
+        loop {
+            match self.step_budget(&mut budget)? {
+                Budget::Done(Step::Continue) => {}
+                Budget::Done(Step::NeedInput) => break Ok(Budget::Done(Run::NeedInput)),
+                Budget::Done(Step::Output(value)) => break Ok(Budget::Done(Run::Output(value))),
+                Budget::Done(Step::Halt) => break Ok(Budget::Done(Run::Halt)),
+                Budget::Exhausted => break Ok(Budget::Exhausted),
+                Budget::Loop => break Ok(Budget::Loop),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::parse;
+
+    #[test]
+    fn test_exhausted() {
+        let mut cpu = CPU::new(parse(r#"1105,1,0"#), 0, None);
+
+        assert!(matches!(cpu.run_with_budget(10), Ok(Budget::Exhausted)));
+        assert!(matches!(cpu.run_with_budget(0), Ok(Budget::Exhausted)));
+    }
+
+    #[test]
+    fn test_done() {
+        let mut cpu = CPU::new(parse(r#"3,9,4,9,4,9,99,0,0,0"#), 0, Some(5));
+
+        assert!(matches!(
+            cpu.run_with_budget(2),
+            Ok(Budget::Done(Run::Output(5)))
+        ));
+        assert!(matches!(
+            cpu.run_with_budget(1),
+            Ok(Budget::Done(Run::Output(5)))
+        ));
+        assert!(matches!(cpu.run_with_budget(0), Ok(Budget::Exhausted)));
+        assert!(matches!(
+            cpu.run_with_budget(1),
+            Ok(Budget::Done(Run::Halt))
+        ));
+    }
+
+    #[test]
+    fn test_resume() {
+        // counts to 100 and outputs it
+        let program = assemble(
+            r"
+loop:   add  [n], #1, [n]
+        lt   [n], #100, [flag]
+        jnz  [flag], #loop
+        out  [n]
+        hlt
+n:      data 0
+flag:   data 0
+",
+        )
+        .unwrap();
+        let mut cpu = CPU::new(program, 0, None);
+        cpu.enable_loop_detection();
+
+        let mut runs = 0;
+        let value = loop {
+            match cpu.run_with_budget(7).unwrap() {
+                Budget::Exhausted => runs += 1,
+                Budget::Done(Run::Output(value)) => break value,
+                state => panic!("invalid state {:?}", state),
+            }
+        };
+
+        assert_eq!(value, 100);
+        assert_eq!(runs, 300 / 7);
+    }
+
+    #[test]
+    fn test_loop() {
+        let mut cpu = CPU::new(parse(r#"1105,1,0"#), 0, None);
+        cpu.enable_loop_detection();
+
+        assert!(matches!(cpu.run_with_budget(1000), Ok(Budget::Loop)));
+    }
+
+    #[test]
+    fn test_loop_with_writes() {
+        // toggles a flag forever: the memory changes but the states repeat
+        let program = assemble(
+            r"
+loop:   eq   [flag], #0, [flag]
+        arb  #1
+        arb  #-1
+        jmp  #loop
+flag:   data 0
+",
+        )
+        .unwrap();
+        let mut cpu = CPU::new(program, 0, None);
+
+        assert!(matches!(cpu.run_with_budget(1000), Ok(Budget::Exhausted)));
+
+        cpu.enable_loop_detection();
+        assert!(matches!(cpu.run_with_budget(1000), Ok(Budget::Loop)));
+    }
+
+    #[test]
+    fn test_io_is_not_a_loop() {
+        let mut cpu = CPU::new(parse(r#"104,1,1105,1,0"#), 0, None);
+        cpu.enable_loop_detection();
+
+        for _ in 0..100 {
+            assert!(matches!(
+                cpu.run_with_budget(1000),
+                Ok(Budget::Done(Run::Output(1)))
+            ));
+        }
+
+        let mut cpu = CPU::new(parse(r#"3,7,1105,1,0,0,0,0"#), 0, None);
+        cpu.enable_loop_detection();
+        cpu.extend_input(vec![0; 100]);
+
+        assert!(matches!(
+            cpu.run_with_budget(1000),
+            Ok(Budget::Done(Run::NeedInput))
+        ));
+    }
+}
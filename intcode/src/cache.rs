@@ -0,0 +1,153 @@
+use crate::word::Word;
+use crate::{Opcode, CPU};
+
+// instructions past this address are decoded on every step
+const CACHED_ADDRESSES: usize = 1 << 16;
+
+// the opcodes already decoded and checked against the isa, by address;
+// writing to an address forgets its entry
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecodeCache {
+    entries: Vec<Option<Opcode>>,
+}
+
+impl DecodeCache {
+    #[inline(always)]
+    pub(crate) fn get(&self, address: usize) -> Option<Opcode> {
+        self.entries.get(address).copied().flatten()
+    }
+
+    #[inline(always)]
+    pub(crate) fn insert(&mut self, address: usize, opcode: Opcode) {
+        if address >= CACHED_ADDRESSES {
+            return;
+        }
+        if address >= self.entries.len() {
+            self.entries.resize(address + 1, None);
+        }
+        self.entries[address] = Some(opcode);
+    }
+
+    #[inline(always)]
+    pub(crate) fn invalidate(&mut self, address: usize) {
+        if let Some(entry) = self.entries.get_mut(address) {
+            *entry = None;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_some()).count()
+    }
+}
+
+impl<W: Word> CPU<W> {
+    // decodes each instruction once, until the program writes over it
+    pub fn enable_decode_cache(&mut self) {
+        self.cache.get_or_insert_with(Box::default);
+    }
+
+    pub fn disable_decode_cache(&mut self) {
+        self.cache = None;
+    }
+
+    pub fn decode_cache_enabled(&self) -> bool {
+        self.cache.is_some()
+    }
+
+    // the instructions currently in the cache
+    pub fn decoded_instructions(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::isa::Isa;
+    use crate::{parse, Error, Run, CPU};
+
+    const DAY09: &str = include_str!("../../day09/data.txt");
+
+    fn outputs(cpu: &mut CPU) -> Result<Vec<i128>, Error> {
+        let mut outputs = vec![];
+        loop {
+            match cpu.run()? {
+                Run::Output(value) => outputs.push(value),
+                _ => break Ok(outputs),
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_as_decoder() {
+        for input in [1, 2] {
+            let mut cpu = CPU::new(parse(DAY09), 0, Some(input));
+            let mut cached = cpu.fork();
+            cached.enable_decode_cache();
+
+            assert_eq!(outputs(&mut cached).unwrap(), outputs(&mut cpu).unwrap());
+            assert!(cached.decoded_instructions() > 0);
+        }
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // outputs 7, then writes a halt over the output and jumps back to it
+        let mut cpu = CPU::new(parse("104,7,1101,1,98,0,1105,1,0"), 0, None);
+        cpu.enable_decode_cache();
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(7))));
+        assert_eq!(cpu.decoded_instructions(), 1);
+
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+        assert_eq!((cpu.ip(), cpu.memory(0)), (0, 99));
+        assert_eq!(cpu.decoded_instructions(), 3);
+    }
+
+    #[test]
+    fn test_toggle() {
+        let mut cpu = CPU::new(parse("1101,1,1,0,99"), 0, None);
+        assert!(!cpu.decode_cache_enabled());
+
+        cpu.enable_decode_cache();
+        cpu.step().unwrap();
+        // the add wrote over itself
+        assert_eq!(cpu.decoded_instructions(), 0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.decoded_instructions(), 1);
+
+        cpu.disable_decode_cache();
+        assert!(!cpu.decode_cache_enabled());
+        assert_eq!(cpu.decoded_instructions(), 0);
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+    }
+
+    #[test]
+    fn test_isa_change() {
+        let mut cpu = CPU::new(parse("1105,0,0,99"), 0, None);
+        cpu.enable_decode_cache();
+        cpu.step().unwrap();
+        assert_eq!(cpu.decoded_instructions(), 1);
+
+        cpu.set_isa(Isa::Day2);
+        assert_eq!(cpu.decoded_instructions(), 0);
+    }
+
+    #[test]
+    fn test_step_back() {
+        // writes the halt it then runs
+        let mut cpu = CPU::new(parse("1101,0,99,4,0"), 0, None);
+        cpu.enable_journal(10);
+        cpu.enable_decode_cache();
+
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+        assert_eq!(cpu.decoded_instructions(), 2);
+
+        assert!(cpu.step_back());
+        assert_eq!(cpu.memory(4), 0);
+        assert_eq!(cpu.decoded_instructions(), 1);
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+use crate::disasm::Line;
+use crate::{Error, Memory, Mode, Opcode, Step, CPU};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operand {
+    Ip,
+    Base,
+    Position(Memory),
+    Relative(Memory),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Comparison {
+    Eq,
+    NEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Condition {
+    operand: Operand,
+    comparison: Comparison,
+    value: Memory,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    Step,
+    Breakpoint(usize),
+    Watchpoint {
+        address: usize,
+        old: Memory,
+        new: Memory,
+    },
+    NeedInput,
+    Output(Memory),
+    Halt,
+}
+
+pub struct Debugger {
+    cpu: CPU,
+    breakpoints: BTreeMap<usize, Option<Condition>>,
+    watchpoints: BTreeSet<usize>,
+    output: Vec<Memory>,
+}
+
+impl FromStr for Operand {
+    type Err = &'static str;
+
+    fn from_str(operand: &str) -> Result<Self, &'static str> {
+        let value = |value: &str| value.parse::<Memory>().map_err(|_| "invalid value");
+
+        match operand {
+            "ip" => Ok(Operand::Ip),
+            "rb" => Ok(Operand::Base),
+            _ => {
+                if let Some(address) = operand
+                    .strip_prefix('[')
+                    .and_then(|address| address.strip_suffix(']'))
+                {
+                    Ok(Operand::Position(value(address)?))
+                } else if let Some(offset) = operand.strip_prefix("rb+") {
+                    Ok(Operand::Relative(value(offset)?))
+                } else if let Some(offset) = operand.strip_prefix("rb-") {
+                    Ok(Operand::Relative(value(&format!("-{}", offset))?))
+                } else {
+                    Err("invalid operand")
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = &'static str;
+
+    fn from_str(comparison: &str) -> Result<Self, &'static str> {
+        match comparison {
+            "==" => Ok(Comparison::Eq),
+            "!=" => Ok(Comparison::NEq),
+            "<" => Ok(Comparison::Less),
+            "<=" => Ok(Comparison::LessEq),
+            ">" => Ok(Comparison::Greater),
+            ">=" => Ok(Comparison::GreaterEq),
+            _ => Err("invalid comparison"),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = &'static str;
+
+    fn from_str(condition: &str) -> Result<Self, &'static str> {
+        let mut tokens = condition.split_whitespace();
+        match (tokens.next(), tokens.next(), tokens.next(), tokens.next()) {
+            (Some(operand), Some(comparison), Some(value), None) => Ok(Condition {
+                operand: operand.parse()?,
+                comparison: comparison.parse()?,
+                value: value.parse().map_err(|_| "invalid value")?,
+            }),
+            _ => Err("invalid condition, expected: <operand> <comparison> <value>"),
+        }
+    }
+}
+
+impl Condition {
+    // false when the operand is a cell outside the address space
+    pub fn eval(&self, cpu: &CPU) -> bool {
+        let cell = |address: Option<Memory>| {
+            address
+                .and_then(|address| usize::try_from(address).ok())
+                .map(|address| cpu.memory[address])
+        };
+        let operand = match self.operand {
+            Operand::Ip => Some(cpu.ip as Memory),
+            Operand::Base => Some(cpu.base),
+            Operand::Position(address) => cell(Some(address)),
+            Operand::Relative(offset) => cell(cpu.base.checked_add(offset)),
+        };
+        let operand = match operand {
+            Some(operand) => operand,
+            None => return false,
+        };
+
+        match self.comparison {
+            Comparison::Eq => operand == self.value,
+            Comparison::NEq => operand != self.value,
+            Comparison::Less => operand < self.value,
+            Comparison::LessEq => operand <= self.value,
+            Comparison::Greater => operand > self.value,
+            Comparison::GreaterEq => operand >= self.value,
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new(cpu: CPU) -> Self {
+        Self {
+            cpu,
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeSet::new(),
+            output: vec![],
+        }
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn ip(&self) -> usize {
+        self.cpu.ip
+    }
+
+    pub fn base(&self) -> Memory {
+        self.cpu.base
+    }
+
+    pub fn memory(&self, address: usize) -> Memory {
+        self.cpu.memory[address]
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (&usize, &Option<Condition>)> {
+        self.breakpoints.iter()
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize, condition: Option<Condition>) {
+        self.breakpoints.insert(address, condition);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn push_input(&mut self, value: Memory) {
+        self.cpu.push_input(value);
+    }
+
+    pub fn take_output(&mut self) -> Vec<Memory> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn instruction(&self, address: usize) -> Line {
+        self.cpu.instruction(address)
+    }
+
+    fn write_target(&self) -> Option<usize> {
+        let (index, mode) = match Opcode::from(&self.cpu.memory[self.cpu.ip], self.cpu.ip).ok()? {
+            Opcode::Add(_, _, mode)
+            | Opcode::Mul(_, _, mode)
+            | Opcode::IfLess(_, _, mode)
+            | Opcode::IfEqTo(_, _, mode) => (3, mode),
+            Opcode::Input(mode) => (1, mode),
+            _ => return None,
+        };
+
+        let value = self.cpu.memory[self.cpu.ip + index];
+        match mode {
+            Mode::Position => usize::try_from(value).ok(),
+            Mode::Relative => self
+                .cpu
+                .base
+                .checked_add(value)
+                .and_then(|address| usize::try_from(address).ok()),
+            Mode::Immediate => None,
+        }
+    }
+
+    fn is_breakpoint(&self) -> bool {
+        match self.breakpoints.get(&self.cpu.ip) {
+            Some(Some(condition)) => condition.eval(&self.cpu),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    pub fn step(&mut self) -> Result<Event, Error> {
+        let watched = self
+            .write_target()
+            .filter(|address| self.watchpoints.contains(address))
+            .map(|address| (address, self.cpu.memory[address]));
+
+        match self.cpu.step()? {
+            Step::Continue => Ok(match watched {
+                Some((address, old)) => Event::Watchpoint {
+                    address,
+                    old,
+                    new: self.cpu.memory[address],
+                },
+                None => Event::Step,
+            }),
+            Step::NeedInput => Ok(Event::NeedInput),
+            Step::Output(value) => {
+                self.output.push(value);
+                Ok(Event::Output(value))
+            }
+            Step::Halt => Ok(Event::Halt),
+        }
+    }
+
+    fn run_until<F>(&mut self, stop: F) -> Result<Event, Error>
+    where
+        F: Fn(&Self, &Event) -> bool,
+    {
+        loop {
+            let event = self.step()?;
+            match event {
+                Event::Step | Event::Output(_) if !stop(self, &event) => {
+                    if self.is_breakpoint() {
+                        break Ok(Event::Breakpoint(self.cpu.ip));
+                    }
+                }
+                _ => break Ok(event),
+            }
+        }
+    }
+
+    pub fn cont(&mut self) -> Result<Event, Error> {
+        self.run_until(|_, _| false)
+    }
+
+    pub fn run_until_output(&mut self) -> Result<Event, Error> {
+        self.run_until(|_, event| matches!(event, Event::Output(_)))
+    }
+
+    // runs until the instruction after the current one is reached with the
+    // same relative base, so a call returns before stopping
+    pub fn step_over(&mut self) -> Result<Event, Error> {
+        let next = self.cpu.ip + self.instruction(self.cpu.ip).words();
+        let base = self.cpu.base;
+
+        self.run_until(|debugger, event| {
+            matches!(event, Event::Step) && debugger.cpu.ip == next && debugger.cpu.base == base
+        })
+        .map(|event| match event {
+            Event::Breakpoint(address) if address == next && self.cpu.base == base => Event::Step,
+            event => event,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn debugger(source: &str) -> Debugger {
+        Debugger::new(CPU::new(
+            assemble(source).expect("invalid program"),
+            0,
+            None,
+        ))
+    }
+
+    const COUNTER: &str = r"
+loop:   add  [counter], #1, [counter]
+        out  [counter]
+        lt   [counter], #5, [flag]
+        jnz  [flag], #loop
+        hlt
+counter: data 0
+flag:   data 0
+";
+
+    #[test]
+    fn test_breakpoint() {
+        let mut debugger = debugger(COUNTER);
+        debugger.add_breakpoint(4, None);
+
+        assert_eq!(debugger.cont().unwrap(), Event::Breakpoint(4));
+        assert_eq!(debugger.memory(14), 1);
+        assert_eq!(debugger.cont().unwrap(), Event::Breakpoint(4));
+        assert_eq!(debugger.memory(14), 2);
+        assert_eq!(debugger.take_output(), vec![1]);
+
+        assert!(debugger.remove_breakpoint(4));
+        assert_eq!(debugger.cont().unwrap(), Event::Halt);
+        assert_eq!(debugger.take_output(), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut debugger = debugger(COUNTER);
+        debugger.add_breakpoint(6, Some("[14] >= 3".parse().unwrap()));
+
+        assert_eq!(debugger.cont().unwrap(), Event::Breakpoint(6));
+        assert_eq!(debugger.memory(14), 3);
+        assert_eq!(debugger.ip(), 6);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut debugger = debugger(COUNTER);
+        debugger.add_watchpoint(15);
+
+        assert_eq!(
+            debugger.cont().unwrap(),
+            Event::Watchpoint {
+                address: 15,
+                old: 0,
+                new: 1
+            }
+        );
+        assert_eq!(debugger.ip(), 10);
+    }
+
+    #[test]
+    fn test_run_until_output() {
+        let mut debugger = debugger(COUNTER);
+
+        assert_eq!(debugger.run_until_output().unwrap(), Event::Output(1));
+        assert_eq!(debugger.run_until_output().unwrap(), Event::Output(2));
+        assert_eq!(debugger.ip(), 6);
+    }
+
+    #[test]
+    fn test_step_over() {
+        let mut debugger = debugger(
+            r"
+        arb  #100
+        call #double
+        out  rb+1
+        hlt
+double: mul  rb+1, #2, rb+1
+        ret
+",
+        );
+        debugger.cpu.memory[101] = 21;
+
+        assert_eq!(debugger.step().unwrap(), Event::Step);
+        assert_eq!(debugger.step_over().unwrap(), Event::Step);
+        assert_eq!(debugger.ip(), 6);
+        assert_eq!(debugger.step_over().unwrap(), Event::Step);
+        assert_eq!(debugger.ip(), 9);
+        assert_eq!(debugger.memory(101), 42);
+        assert_eq!(debugger.step().unwrap(), Event::Output(42));
+    }
+
+    #[test]
+    fn test_input() {
+        let mut debugger = debugger("in [5]\nout [5]\nhlt");
+
+        assert_eq!(debugger.cont().unwrap(), Event::NeedInput);
+        debugger.push_input(7);
+        assert_eq!(debugger.cont().unwrap(), Event::Halt);
+        assert_eq!(debugger.take_output(), vec![7]);
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        assert_eq!(
+            "rb-2 != 0".parse::<Condition>(),
+            Ok(Condition {
+                operand: Operand::Relative(-2),
+                comparison: Comparison::NEq,
+                value: 0
+            })
+        );
+        assert!("ip".parse::<Condition>().is_err());
+        assert!("ip ~ 2".parse::<Condition>().is_err());
+        assert!("rb--2 != 0".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_condition_out_of_range() {
+        let mut debugger = debugger("arb #1\nhlt");
+        debugger.step().unwrap();
+
+        let eval = |condition: &str| condition.parse::<Condition>().unwrap().eval(&debugger.cpu);
+
+        assert!(eval("rb+0 == 1"));
+        assert!(!eval(&format!("rb+{} == 0", Memory::MAX)));
+        assert!(!eval("rb-2 == 0"));
+        assert!(!eval("[-1] == 0"));
+        assert!(!eval("[-1] != 0"));
+    }
+
+    #[test]
+    fn test_watchpoint_out_of_range() {
+        let mut debugger = debugger(&format!("arb #1\nadd #1, #1, rb+{}\nhlt", Memory::MAX));
+        debugger.add_watchpoint(0);
+        debugger.step().unwrap();
+
+        assert!(matches!(debugger.step(), Err(Error::Overflow(..))));
+    }
+}
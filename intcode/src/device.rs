@@ -0,0 +1,366 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::ascii::AsciiError;
+use crate::{Error, Memory, Run, CPU};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Pause,
+}
+
+pub trait IoDevice {
+    // None pauses the cpu until some input is available
+    fn input(&mut self) -> Option<Memory>;
+
+    fn output(&mut self, value: Memory) -> Control;
+
+    fn halt(&mut self) {}
+}
+
+impl CPU {
+    // runs until the program halts, the device has no input for it or the
+    // device pauses after an output
+    pub fn run_with<D: IoDevice>(&mut self, device: &mut D) -> Result<Run, Error> {
+        loop {
+            match self.run()? {
+                Run::NeedInput => match device.input() {
+                    Some(value) => self.push_input(value),
+                    None => break Ok(Run::NeedInput),
+                },
+                Run::Output(value) => {
+                    if device.output(value) == Control::Pause {
+                        break Ok(Run::Output(value));
+                    }
+                }
+                Run::Halt => {
+                    device.halt();
+                    break Ok(Run::Halt);
+                }
+            }
+        }
+    }
+}
+
+// input from the first device, output to the second one
+impl<I: IoDevice, O: IoDevice> IoDevice for (I, O) {
+    fn input(&mut self) -> Option<Memory> {
+        self.0.input()
+    }
+
+    fn output(&mut self, value: Memory) -> Control {
+        self.1.output(value)
+    }
+
+    fn halt(&mut self) {
+        self.0.halt();
+        self.1.halt();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Feeder {
+    values: VecDeque<Memory>,
+}
+
+impl Feeder {
+    pub fn new<I: IntoIterator<Item = Memory>>(values: I) -> Self {
+        Self {
+            values: values.into_iter().collect(),
+        }
+    }
+
+    pub fn push(&mut self, value: Memory) {
+        self.values.push_back(value);
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.values.len()
+    }
+}
+
+impl IoDevice for Feeder {
+    fn input(&mut self) -> Option<Memory> {
+        self.values.pop_front()
+    }
+
+    fn output(&mut self, _: Memory) -> Control {
+        Control::Continue
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Collector {
+    values: Vec<Memory>,
+    halted: bool,
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn values(&self) -> &[Memory] {
+        &self.values
+    }
+
+    pub fn take(&mut self) -> Vec<Memory> {
+        std::mem::take(&mut self.values)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+}
+
+impl IoDevice for Collector {
+    fn input(&mut self) -> Option<Memory> {
+        None
+    }
+
+    fn output(&mut self, value: Memory) -> Control {
+        self.values.push(value);
+        Control::Continue
+    }
+
+    fn halt(&mut self) {
+        self.halted = true;
+    }
+}
+
+// blocks on the receiver for input, a closed channel pauses the cpu
+pub struct Channel {
+    input: Receiver<Memory>,
+    output: Sender<Memory>,
+}
+
+impl Channel {
+    pub fn new(input: Receiver<Memory>, output: Sender<Memory>) -> Self {
+        Self { input, output }
+    }
+}
+
+impl IoDevice for Channel {
+    fn input(&mut self) -> Option<Memory> {
+        self.input.recv().ok()
+    }
+
+    fn output(&mut self, value: Memory) -> Control {
+        match self.output.send(value) {
+            Ok(()) => Control::Continue,
+            Err(_) => Control::Pause,
+        }
+    }
+}
+
+// reads lines of ascii text and prints ascii output, values that are not
+// ascii are printed on a line of their own; a line that is not ascii stops
+// the input and is kept as the error
+pub struct Console<R, W> {
+    reader: R,
+    writer: W,
+    line: VecDeque<Memory>,
+    error: Option<AsciiError>,
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            line: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&AsciiError> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R: BufRead, W: Write> IoDevice for Console<R, W> {
+    fn input(&mut self) -> Option<Memory> {
+        if self.line.is_empty() {
+            self.writer.flush().ok()?;
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            if let Some(c) = line.chars().find(|c| !c.is_ascii()) {
+                self.error = Some(AsciiError::NonAscii(c));
+                return None;
+            }
+            if !line.ends_with('\n') {
+                line.push('\n');
+            }
+            self.line.extend(line.bytes().map(Memory::from));
+        }
+
+        self.line.pop_front()
+    }
+
+    fn output(&mut self, value: Memory) -> Control {
+        let result = match u8::try_from(value) {
+            Ok(c) if c.is_ascii() => self.writer.write_all(&[c]),
+            _ => writeln!(self.writer, "{}", value),
+        };
+
+        match result {
+            Ok(()) => Control::Continue,
+            Err(_) => Control::Pause,
+        }
+    }
+
+    fn halt(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::parse;
+    use std::sync::mpsc;
+    use std::thread;
+
+    // outputs a + 10 * b
+    const SUM: &str = r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#;
+
+    #[test]
+    fn test_feeder_and_collector() {
+        let mut cpu = CPU::new(parse(SUM), 0, None);
+        let mut device = (Feeder::new(vec![3, 4]), Collector::new());
+
+        assert!(matches!(cpu.run_with(&mut device), Ok(Run::Halt)));
+        assert_eq!(device.0.remaining(), 0);
+        assert_eq!(device.1.values(), &[43]);
+        assert!(device.1.is_halted());
+    }
+
+    #[test]
+    fn test_need_input() {
+        let mut cpu = CPU::new(parse(SUM), 0, None);
+        let mut device = (Feeder::new(vec![3]), Collector::new());
+
+        assert!(matches!(cpu.run_with(&mut device), Ok(Run::NeedInput)));
+
+        device.0.push(5);
+        assert!(matches!(cpu.run_with(&mut device), Ok(Run::Halt)));
+        assert_eq!(device.1.take(), vec![53]);
+    }
+
+    #[test]
+    fn test_pause() {
+        struct Pairs(Vec<(Memory, Memory)>, Option<Memory>);
+
+        impl IoDevice for Pairs {
+            fn input(&mut self) -> Option<Memory> {
+                None
+            }
+
+            fn output(&mut self, value: Memory) -> Control {
+                match self.1.take() {
+                    Some(first) => {
+                        self.0.push((first, value));
+                        Control::Pause
+                    }
+                    None => {
+                        self.1 = Some(value);
+                        Control::Continue
+                    }
+                }
+            }
+        }
+
+        let mut cpu = CPU::new(parse(r#"104,1,104,2,104,3,104,4,99"#), 0, None);
+        let mut pairs = Pairs(vec![], None);
+
+        assert!(matches!(cpu.run_with(&mut pairs), Ok(Run::Output(2))));
+        assert_eq!(pairs.0, vec![(1, 2)]);
+        assert!(matches!(cpu.run_with(&mut pairs), Ok(Run::Output(4))));
+        assert!(matches!(cpu.run_with(&mut pairs), Ok(Run::Halt)));
+        assert_eq!(pairs.0, vec![(1, 2), (3, 4)]);
+    }
+
+    #[test]
+    fn test_channel() {
+        let (input_tx, input_rx) = mpsc::channel();
+        let (output_tx, output_rx) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            let mut cpu = CPU::new(parse(SUM), 0, None);
+            cpu.run_with(&mut Channel::new(input_rx, output_tx))
+        });
+
+        input_tx.send(1).unwrap();
+        input_tx.send(2).unwrap();
+
+        assert_eq!(output_rx.recv().unwrap(), 21);
+        assert!(matches!(handle.join().unwrap(), Ok(Run::Halt)));
+    }
+
+    #[test]
+    fn test_closed_channel() {
+        let (input_tx, input_rx) = mpsc::channel();
+        let (output_tx, _) = mpsc::channel();
+        drop(input_tx);
+
+        let mut cpu = CPU::new(parse(SUM), 0, None);
+        assert!(matches!(
+            cpu.run_with(&mut Channel::new(input_rx, output_tx)),
+            Ok(Run::NeedInput)
+        ));
+    }
+
+    #[test]
+    fn test_console() {
+        // echoes a line, then prints its length
+        let program = assemble(
+            r"
+next:   in   [c]
+        eq   [c], #10, [flag]
+        jnz  [flag], #done
+        out  [c]
+        add  [count], #1000, [count]
+        jmp  #next
+done:   out  #10
+        out  [count]
+        hlt
+c:      data 0
+count:  data 0
+flag:   data 0
+",
+        )
+        .unwrap();
+
+        let mut cpu = CPU::new(program, 0, None);
+        let mut console = Console::new("hello\nignored\n".as_bytes(), vec![]);
+
+        assert!(matches!(cpu.run_with(&mut console), Ok(Run::Halt)));
+        assert!(console.error().is_none());
+
+        let (rest, output) = console.into_inner();
+        assert_eq!(String::from_utf8(output).unwrap(), "hello\n5000\n");
+        assert_eq!(rest, b"ignored\n");
+    }
+
+    #[test]
+    fn test_console_non_ascii() {
+        let mut cpu = CPU::new(parse("3,9,4,9,1105,1,0,99,99,0"), 0, None);
+        let mut console = Console::new("ok\ntàke\n".as_bytes(), vec![]);
+
+        assert!(matches!(cpu.run_with(&mut console), Ok(Run::NeedInput)));
+        assert!(matches!(console.error(), Some(AsciiError::NonAscii('à'))));
+
+        let (_, output) = console.into_inner();
+        assert_eq!(String::from_utf8(output).unwrap(), "ok\n");
+    }
+}
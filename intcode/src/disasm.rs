@@ -0,0 +1,646 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+use crate::{Memory, Mode, Opcode, CPU};
+
+const DATA_PER_LINE: usize = 8;
+const COMMENT_COLUMN: usize = 40;
+// the zeros a listing can skip between two lines
+const MAX_GAP: usize = 1 << 16;
+
+pub(crate) const MNEMONICS: [(&str, Memory, usize); 10] = [
+    ("add", 1, 3),
+    ("mul", 2, 3),
+    ("in", 3, 1),
+    ("out", 4, 1),
+    ("jnz", 5, 2),
+    ("jz", 6, 2),
+    ("lt", 7, 3),
+    ("eq", 8, 3),
+    ("arb", 9, 1),
+    ("hlt", 99, 0),
+];
+
+#[derive(Debug, Clone)]
+pub enum Line {
+    Instruction {
+        address: usize,
+        opcode: Opcode,
+        parameters: Vec<Memory>,
+    },
+    Data {
+        address: usize,
+        values: Vec<Memory>,
+    },
+}
+
+#[derive(Debug)]
+pub enum ListingError {
+    InvalidAddress(usize, String),
+    AddressOutOfOrder(usize, usize),
+    AddressTooFar(usize, usize),
+    InvalidMnemonic(usize, String),
+    InvalidOperand(usize, String),
+    InvalidOperandCount(usize, usize),
+}
+
+impl fmt::Display for ListingError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ListingError::InvalidAddress(line, token) => {
+                write!(fmt, "line {}: invalid address {:?}", line, token)
+            }
+            ListingError::AddressOutOfOrder(line, address) => {
+                write!(fmt, "line {}: address {} out of order", line, address)
+            }
+            ListingError::AddressTooFar(line, address) => {
+                write!(fmt, "line {}: address {} too far", line, address)
+            }
+            ListingError::InvalidMnemonic(line, token) => {
+                write!(fmt, "line {}: invalid mnemonic {:?}", line, token)
+            }
+            ListingError::InvalidOperand(line, token) => {
+                write!(fmt, "line {}: invalid operand {:?}", line, token)
+            }
+            ListingError::InvalidOperandCount(line, count) => {
+                write!(fmt, "line {}: invalid operand count {}", line, count)
+            }
+        }
+    }
+}
+
+impl error::Error for ListingError {}
+
+pub struct Listing {
+    lines: Vec<Line>,
+    entries: BTreeMap<usize, Vec<usize>>,
+}
+
+type Code = BTreeMap<usize, (Opcode, Vec<Memory>)>;
+
+pub(crate) fn mnemonic(opcode: &Opcode) -> &'static str {
+    MNEMONICS
+        .iter()
+        .find(|(_, code, _)| *code == opcode.code())
+        .map(|(mnemonic, _, _)| *mnemonic)
+        .expect("unknown opcode")
+}
+
+pub(crate) fn operand(mode: Mode, value: Memory) -> String {
+    match mode {
+        Mode::Position => format!("[{}]", value),
+        Mode::Immediate => format!("#{}", value),
+        Mode::Relative if value < 0 => format!("rb-{}", value.unsigned_abs()),
+        Mode::Relative => format!("rb+{}", value),
+    }
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+
+    pub fn words(&self) -> usize {
+        match self {
+            Line::Instruction { parameters, .. } => parameters.len() + 1,
+            Line::Data { values, .. } => values.len(),
+        }
+    }
+
+    pub fn target(&self) -> Option<usize> {
+        match self {
+            Line::Instruction {
+                opcode: Opcode::IfNEq(_, Mode::Immediate) | Opcode::IfEq(_, Mode::Immediate),
+                parameters,
+                ..
+            } if parameters[1] >= 0 => Some(parameters[1] as usize),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Line::Instruction {
+                opcode, parameters, ..
+            } => {
+                let operands = opcode
+                    .modes()
+                    .into_iter()
+                    .zip(parameters)
+                    .map(|(mode, value)| operand(mode, *value))
+                    .collect::<Vec<_>>();
+                if operands.is_empty() {
+                    fmt.write_str(mnemonic(opcode))
+                } else {
+                    write!(fmt, "{:<4}{}", mnemonic(opcode), operands.join(", "))
+                }
+            }
+            Line::Data { values, .. } => write!(
+                fmt,
+                "data {}",
+                values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl Listing {
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn entries(&self, address: usize) -> &[usize] {
+        self.entries
+            .get(&address)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn write_annotated<F>(&self, fmt: &mut dyn fmt::Write, annotate: F) -> fmt::Result
+    where
+        F: Fn(&Line) -> Option<String>,
+    {
+        for line in &self.lines {
+            let mut comments = vec![];
+            if let Some(target) = line.target() {
+                comments.push(format!("-> {}", target));
+            }
+            let entries = self.entries(line.address());
+            if !entries.is_empty() {
+                comments.push(format!(
+                    "<- {}",
+                    entries
+                        .iter()
+                        .map(|address| address.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+            if let Some(annotation) = annotate(line) {
+                comments.push(annotation);
+            }
+
+            let body = format!("{:>5}: {}", line.address(), line);
+            if comments.is_empty() {
+                writeln!(fmt, "{}", body)?;
+            } else {
+                writeln!(
+                    fmt,
+                    "{:<width$} ; {}",
+                    body,
+                    comments.join("; "),
+                    width = COMMENT_COLUMN
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.write_annotated(fmt, |_| None)
+    }
+}
+
+impl CPU {
+    // the instruction at address as the cpu would decode it now, the word
+    // as data when it is not an instruction
+    pub fn instruction(&self, address: usize) -> Line {
+        let word = self.memory[address];
+        match Opcode::from(&word, address) {
+            Ok(opcode) => Line::Instruction {
+                address,
+                opcode,
+                parameters: (1..=opcode.modes().len())
+                    .map(|offset| self.memory[address + offset])
+                    .collect(),
+            },
+            Err(_) => Line::Data {
+                address,
+                values: vec![word],
+            },
+        }
+    }
+}
+
+pub(crate) fn decode(program: &[Memory], address: usize) -> Option<(Opcode, Vec<Memory>)> {
+    let word = *program.get(address)?;
+    let opcode = Opcode::from(&word, address).ok()?;
+    if opcode.encode() != word {
+        return None;
+    }
+
+    let parameters = program
+        .get(address + 1..address + 1 + opcode.modes().len())?
+        .to_vec();
+
+    Some((opcode, parameters))
+}
+
+fn successors(address: usize, opcode: &Opcode, parameters: &[Memory]) -> Vec<usize> {
+    let next = address + parameters.len() + 1;
+    let jump = |mode, taken: fn(Memory) -> bool| {
+        let target = match mode {
+            Mode::Immediate if parameters[1] >= 0 => Some(parameters[1] as usize),
+            _ => None,
+        };
+        match (opcode.modes()[0], target) {
+            (Mode::Immediate, Some(target)) if taken(parameters[0]) => vec![target],
+            (Mode::Immediate, _) if taken(parameters[0]) => vec![],
+            (Mode::Immediate, _) => vec![next],
+            (_, Some(target)) => vec![next, target],
+            (_, None) => vec![next],
+        }
+    };
+
+    match *opcode {
+        Opcode::Halt => vec![],
+        Opcode::IfNEq(_, mode) => jump(mode, |value| value != 0),
+        Opcode::IfEq(_, mode) => jump(mode, |value| value == 0),
+        _ => vec![next],
+    }
+}
+
+// follows the control flow from seed: with strict set the whole trace is
+// rejected when it reaches a word that does not decode, otherwise that
+// path just ends there
+fn trace(program: &[Memory], code: &Code, seed: usize, strict: bool) -> Option<Code> {
+    let claimed = |found: &Code, address: usize| {
+        [code, found].iter().any(|code| {
+            code.range(..=address)
+                .next_back()
+                .map(|(start, (_, parameters))| address <= start + parameters.len())
+                .unwrap_or(false)
+        })
+    };
+
+    let mut found = Code::new();
+    let mut queue = vec![seed];
+    while let Some(address) = queue.pop() {
+        if code.contains_key(&address) || found.contains_key(&address) {
+            continue;
+        }
+
+        match decode(program, address) {
+            Some((opcode, parameters))
+                if (address..=address + parameters.len())
+                    .all(|address| !claimed(&found, address)) =>
+            {
+                queue.extend(successors(address, &opcode, &parameters));
+                found.insert(address, (opcode, parameters));
+            }
+            _ if strict => return None,
+            _ => {}
+        }
+    }
+
+    Some(found)
+}
+
+// constants built by add or mul with two immediate operands are usually
+// return addresses pushed before a call
+fn pointers(program: &[Memory], code: &Code) -> Vec<usize> {
+    code.values()
+        .filter_map(|(opcode, parameters)| match opcode {
+            Opcode::Add(Mode::Immediate, Mode::Immediate, _) => {
+                parameters[0].checked_add(parameters[1])
+            }
+            Opcode::Mul(Mode::Immediate, Mode::Immediate, _) => {
+                parameters[0].checked_mul(parameters[1])
+            }
+            _ => None,
+        })
+        .filter(|&value| value >= 0 && (value as usize) < program.len())
+        .map(|value| value as usize)
+        .collect()
+}
+
+pub fn disassemble(program: &[Memory]) -> Listing {
+    let mut code = trace(program, &Code::new(), 0, false).unwrap_or_default();
+
+    let mut rejected = vec![];
+    loop {
+        let seeds = pointers(program, &code)
+            .into_iter()
+            .filter(|seed| !code.contains_key(seed) && !rejected.contains(seed))
+            .collect::<Vec<_>>();
+        if seeds.is_empty() {
+            break;
+        }
+
+        for seed in seeds {
+            match trace(program, &code, seed, true) {
+                Some(found) => code.extend(found),
+                None => rejected.push(seed),
+            }
+        }
+    }
+
+    let mut lines = vec![];
+    let mut address = 0;
+    while address < program.len() {
+        if let Some((opcode, parameters)) = code.remove(&address) {
+            address += parameters.len() + 1;
+            lines.push(Line::Instruction {
+                address: address - parameters.len() - 1,
+                opcode,
+                parameters,
+            });
+        } else {
+            let end = (address + 1..program.len())
+                .take(DATA_PER_LINE - 1)
+                .find(|address| code.contains_key(address))
+                .unwrap_or_else(|| program.len().min(address + DATA_PER_LINE));
+            lines.push(Line::Data {
+                address,
+                values: program[address..end].to_vec(),
+            });
+            address = end;
+        }
+    }
+
+    let mut entries = BTreeMap::<usize, Vec<usize>>::new();
+    for line in &lines {
+        if let Some(target) = line.target() {
+            entries.entry(target).or_default().push(line.address());
+        }
+    }
+
+    Listing { lines, entries }
+}
+
+fn parse_value(line: usize, value: &str) -> Result<Memory, ListingError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ListingError::InvalidOperand(line, value.to_string()))
+}
+
+fn parse_operand(line: usize, operand: &str) -> Result<(Memory, Memory), ListingError> {
+    let operand = operand.trim();
+    if let Some(value) = operand
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+    {
+        Ok((Mode::Position.code(), parse_value(line, value)?))
+    } else if let Some(value) = operand.strip_prefix('#') {
+        Ok((Mode::Immediate.code(), parse_value(line, value)?))
+    } else if let Some(value) = operand.strip_prefix("rb+") {
+        Ok((Mode::Relative.code(), parse_value(line, value)?))
+    } else if let Some(value) = operand.strip_prefix("rb-") {
+        // parsed with its sign, the most negative offset has no positive
+        Ok((
+            Mode::Relative.code(),
+            parse_value(line, &format!("-{}", value.trim()))?,
+        ))
+    } else {
+        Err(ListingError::InvalidOperand(line, operand.to_string()))
+    }
+}
+
+pub fn parse_listing(listing: &str) -> Result<Vec<Memory>, ListingError> {
+    let mut program = vec![];
+
+    for (index, text) in listing.lines().enumerate() {
+        let line = index + 1;
+        let text = text.split(';').next().unwrap_or_default().trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let (address, body) = text
+            .split_once(':')
+            .ok_or_else(|| ListingError::InvalidAddress(line, text.to_string()))?;
+        let address = address
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| ListingError::InvalidAddress(line, address.to_string()))?;
+        if address < program.len() {
+            return Err(ListingError::AddressOutOfOrder(line, address));
+        }
+        if address - program.len() > MAX_GAP {
+            return Err(ListingError::AddressTooFar(line, address));
+        }
+        program.resize(address, 0);
+
+        let body = body.trim();
+        let (name, operands) = body.split_once(' ').unwrap_or((body, ""));
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .collect::<Vec<_>>();
+
+        if name == "data" {
+            for value in operands {
+                program.push(parse_value(line, value)?);
+            }
+            continue;
+        }
+
+        let (_, code, arity) = MNEMONICS
+            .iter()
+            .find(|(mnemonic, _, _)| *mnemonic == name)
+            .ok_or_else(|| ListingError::InvalidMnemonic(line, name.to_string()))?;
+        if operands.len() != *arity {
+            return Err(ListingError::InvalidOperandCount(line, operands.len()));
+        }
+
+        let operands = operands
+            .into_iter()
+            .map(|operand| parse_operand(line, operand))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        program.push(
+            operands
+                .iter()
+                .zip([100, 1000, 10000])
+                .fold(*code, |acc, ((mode, _), weight)| acc + mode * weight),
+        );
+        program.extend(operands.into_iter().map(|(_, value)| value));
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn round_trip(program: &[Memory]) {
+        let listing = disassemble(program).to_string();
+
+        assert_eq!(
+            parse_listing(&listing).expect("invalid listing"),
+            program,
+            "{}",
+            listing
+        );
+    }
+
+    #[test]
+    fn test_listing() {
+        let program = parse(r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#);
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            r"    0: in  [15]
+    2: in  [16]
+    4: mul [16], #10, [16]
+    8: add [16], [15], [15]
+   12: out [15]
+   14: hlt
+   15: data 0, 0
+"
+        );
+    }
+
+    #[test]
+    fn test_relative_operands() {
+        let program = parse(r#"109,1,204,-1,21101,1,2,3,99"#);
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            r"    0: arb #1
+    2: out rb-1
+    4: add #1, #2, rb+3
+    8: hlt
+"
+        );
+    }
+
+    #[test]
+    fn test_follow_jumps() {
+        // the words after the unconditional jump are never executed
+        let program = parse(r#"1105,1,7,1,2,3,4,104,1,1106,0,13,5,99"#);
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            r"    0: jnz #1, #7                        ; -> 7
+    3: data 1, 2, 3, 4
+    7: out #1                            ; <- 0
+    9: jz  #0, #13                       ; -> 13
+   12: data 5
+   13: hlt                               ; <- 9
+"
+        );
+    }
+
+    #[test]
+    fn test_return_address() {
+        // call 9 with return address 8 stored at rb+0
+        let program = parse(r#"21101,0,8,0,1105,1,9,0,99,1106,0,0"#);
+
+        let listing = disassemble(&program);
+
+        assert!(matches!(
+            listing.lines()[3],
+            Line::Instruction {
+                address: 8,
+                opcode: Opcode::Halt,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_words_are_data() {
+        let program = parse(r#"104,1,199,-5,12345"#);
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            r"    0: out #1
+    2: data 199, -5, 12345
+"
+        );
+    }
+
+    #[test]
+    fn test_huge_immediates() {
+        // neither sum nor product fits, so neither is a pointer
+        disassemble(&[1101, Memory::MAX, 1, 0, 99]);
+        disassemble(&[1102, Memory::MAX, 2, 0, 99]);
+
+        round_trip(&[204, Memory::MIN, 99]);
+    }
+
+    #[test]
+    fn test_parse_listing() {
+        let listing = r"
+; comments and blank lines are ignored
+    0: in  [15]
+    2: out rb-1    ; relative
+    4: jnz #1, #10
+   10: data 1, 2
+";
+
+        assert_eq!(
+            parse_listing(listing).unwrap(),
+            vec![3, 15, 204, -1, 1105, 1, 10, 0, 0, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_parse_listing_errors() {
+        assert!(matches!(
+            parse_listing("0: nop"),
+            Err(ListingError::InvalidMnemonic(1, _))
+        ));
+        assert!(matches!(
+            parse_listing("0: hlt\n0: hlt"),
+            Err(ListingError::AddressOutOfOrder(2, 0))
+        ));
+        assert!(matches!(
+            parse_listing(&format!("0: hlt\n{}: hlt", usize::MAX)),
+            Err(ListingError::AddressTooFar(2, usize::MAX))
+        ));
+        assert!(matches!(
+            parse_listing("\n0: add [1], [2]"),
+            Err(ListingError::InvalidOperandCount(2, 2))
+        ));
+        assert!(matches!(
+            parse_listing("0: out 1"),
+            Err(ListingError::InvalidOperand(1, _))
+        ));
+        assert!(matches!(
+            parse_listing("0: out rb--1"),
+            Err(ListingError::InvalidOperand(1, _))
+        ));
+        assert!(matches!(
+            parse_listing("hlt"),
+            Err(ListingError::InvalidAddress(1, _))
+        ));
+        assert_eq!(
+            parse_listing("0: hlt\n0: hlt").unwrap_err().to_string(),
+            "line 2: address 0 out of order"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(&parse(
+            r#"109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"#,
+        ));
+        round_trip(&parse(r#"1102,34915192,34915192,7,4,7,99,0"#));
+        round_trip(&parse(r#"104,1,199,-5,12345"#));
+    }
+
+    #[test]
+    fn test_round_trip_days() {
+        round_trip(&parse(include_str!("../../day09/data.txt")));
+        round_trip(&parse(include_str!("../../day13/data.txt")));
+        round_trip(&parse(include_str!("../../day25/data.txt")));
+    }
+}
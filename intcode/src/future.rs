@@ -0,0 +1,391 @@
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::{Error, Memory, Run, CPU};
+
+// the minimal stream and sink interfaces the cpu needs, so that any executor
+// and any channel can drive it with a small adapter
+pub trait Stream {
+    type Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+}
+
+pub trait Sink<T> {
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Closed>>;
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Closed>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Closed;
+
+#[derive(Debug)]
+pub enum AsyncError {
+    CPU(Error),
+    InputClosed,
+    OutputClosed,
+}
+
+impl fmt::Display for AsyncError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            AsyncError::CPU(e) => write!(fmt, "cpu error: {}", e),
+            AsyncError::InputClosed => write!(fmt, "input closed while waiting for a value"),
+            AsyncError::OutputClosed => write!(fmt, "output closed while sending a value"),
+        }
+    }
+}
+
+impl error::Error for AsyncError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            AsyncError::CPU(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub struct Machine<S, K> {
+    cpu: Option<CPU>,
+    input: S,
+    output: K,
+    pending: Option<Memory>,
+}
+
+impl CPU {
+    // resolves when the program halts, yielding the cpu back
+    pub fn run_async<S, K>(self, input: S, output: K) -> Machine<S, K>
+    where
+        S: Stream<Item = Memory> + Unpin,
+        K: Sink<Memory> + Unpin,
+    {
+        Machine {
+            cpu: Some(self),
+            input,
+            output,
+            pending: None,
+        }
+    }
+}
+
+impl<S, K> Future for Machine<S, K>
+where
+    S: Stream<Item = Memory> + Unpin,
+    K: Sink<Memory> + Unpin,
+{
+    type Output = Result<CPU, AsyncError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let machine = &mut *self;
+        let cpu = machine
+            .cpu
+            .as_mut()
+            .expect("machine polled after completion");
+        loop {
+            if let Some(value) = machine.pending {
+                match Pin::new(&mut machine.output).poll_ready(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Err(Closed)) => return Poll::Ready(Err(AsyncError::OutputClosed)),
+                    Poll::Ready(Ok(())) => {
+                        Pin::new(&mut machine.output)
+                            .start_send(value)
+                            .map_err(|_| AsyncError::OutputClosed)?;
+                        machine.pending = None;
+                    }
+                }
+            }
+
+            match cpu.run().map_err(AsyncError::CPU)? {
+                Run::Output(value) => machine.pending = Some(value),
+                Run::NeedInput => match Pin::new(&mut machine.input).poll_next(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(None) => return Poll::Ready(Err(AsyncError::InputClosed)),
+                    Poll::Ready(Some(value)) => cpu.push_input(value),
+                },
+                Run::Halt => return Poll::Ready(Ok(machine.cpu.take().unwrap())),
+            }
+        }
+    }
+}
+
+struct Shared {
+    queue: VecDeque<Memory>,
+    waker: Option<Waker>,
+    senders: usize,
+    receiver: bool,
+}
+
+// an unbounded channel, the receiver is a stream and the senders are sinks
+pub fn channel() -> (Sender, Receiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        queue: VecDeque::new(),
+        waker: None,
+        senders: 1,
+        receiver: true,
+    }));
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+pub struct Sender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Sender {
+    pub fn send(&self, value: Memory) -> Result<(), Closed> {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.receiver {
+            return Err(Closed);
+        }
+
+        shared.queue.push_back(value);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Sink<Memory> for Sender {
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Closed>> {
+        if self.shared.lock().unwrap().receiver {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Ready(Err(Closed))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Memory) -> Result<(), Closed> {
+        self.send(item)
+    }
+}
+
+pub struct Receiver {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Receiver {
+    pub fn try_recv(&mut self) -> Option<Memory> {
+        self.shared.lock().unwrap().queue.pop_front()
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.shared.lock().unwrap().receiver = false;
+    }
+}
+
+impl Stream for Receiver {
+    type Item = Memory;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Memory>> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if shared.senders == 0 => Poll::Ready(None),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::Wake;
+
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    type Task<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+    // polls every task that was woken until all of them are done
+    fn run_all<T>(tasks: Vec<Task<'_, T>>) -> Vec<T> {
+        let mut tasks = tasks
+            .into_iter()
+            .map(|task| (Arc::new(Flag(AtomicBool::new(true))), Some(task)))
+            .collect::<Vec<_>>();
+        let mut results = (0..tasks.len()).map(|_| None).collect::<Vec<_>>();
+
+        while results.iter().any(Option::is_none) {
+            let mut progress = false;
+            for (index, (flag, task)) in tasks.iter_mut().enumerate() {
+                if task.is_none() || !flag.0.swap(false, Ordering::SeqCst) {
+                    continue;
+                }
+
+                progress = true;
+                let waker = Waker::from(flag.clone());
+                if let Poll::Ready(result) = task
+                    .as_mut()
+                    .unwrap()
+                    .as_mut()
+                    .poll(&mut Context::from_waker(&waker))
+                {
+                    results[index] = Some(result);
+                    *task = None;
+                }
+            }
+            assert!(progress, "deadlock");
+        }
+
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    // forwards to the sender, remembering the last value: the last one is
+    // sent after the first amplifier halted, so it is dropped
+    struct Tap(Sender, Rc<Cell<Memory>>);
+
+    impl Sink<Memory> for Tap {
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Closed>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(self: Pin<&mut Self>, item: Memory) -> Result<(), Closed> {
+            self.1.set(item);
+            let _ = self.0.send(item);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_async() {
+        let (input_tx, input_rx) = channel();
+        let (output_tx, mut output_rx) = channel();
+
+        // outputs a + 10 * b
+        let cpu = CPU::new(
+            parse(r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#),
+            0,
+            None,
+        );
+
+        input_tx.send(1).unwrap();
+        input_tx.send(2).unwrap();
+
+        let results = run_all(vec![Box::pin(cpu.run_async(input_rx, output_tx))]);
+
+        assert!(results[0].is_ok());
+        assert_eq!(output_rx.try_recv(), Some(21));
+    }
+
+    #[test]
+    fn test_closed() {
+        let program = parse(r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#);
+
+        let (input_tx, input_rx) = channel();
+        let (output_tx, _output_rx) = channel();
+        input_tx.send(1).unwrap();
+        drop(input_tx);
+        let results = run_all(vec![Box::pin(
+            CPU::new(program.to_owned(), 0, None).run_async(input_rx, output_tx),
+        )]);
+        assert!(matches!(results[0], Err(AsyncError::InputClosed)));
+
+        let (input_tx, input_rx) = channel();
+        let (output_tx, output_rx) = channel();
+        input_tx.send(1).unwrap();
+        input_tx.send(2).unwrap();
+        drop(output_rx);
+        let results = run_all(vec![Box::pin(
+            CPU::new(program, 0, None).run_async(input_rx, output_tx),
+        )]);
+        assert!(matches!(results[0], Err(AsyncError::OutputClosed)));
+    }
+
+    fn permutations(values: Vec<Memory>) -> Vec<Vec<Memory>> {
+        if values.len() <= 1 {
+            return vec![values];
+        }
+
+        (0..values.len())
+            .flat_map(|index| {
+                let mut rest = values.to_owned();
+                let value = rest.remove(index);
+                permutations(rest).into_iter().map(move |mut permutation| {
+                    permutation.insert(0, value);
+                    permutation
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_feedback_loops() {
+        // day 7 part 2: 120 loops of 5 amplifiers, all on this thread
+        let program: Vec<Memory> = parse(include_str!("../../day07/data.txt"));
+
+        let mut tasks: Vec<Task<'_, Result<CPU, AsyncError>>> = vec![];
+        let mut results = vec![];
+        for phases in permutations(vec![5, 6, 7, 8, 9]) {
+            let channels = (0..5).map(|_| channel()).collect::<Vec<_>>();
+            let (senders, receivers): (Vec<_>, Vec<_>) = channels.into_iter().unzip();
+            for (sender, phase) in senders.iter().zip(&phases) {
+                sender.send(*phase).unwrap();
+            }
+            senders[0].send(0).unwrap();
+
+            let last = Rc::new(Cell::new(0));
+            for (index, input) in receivers.into_iter().enumerate() {
+                let cpu = CPU::new(program.to_owned(), 0, None);
+                let output = senders[(index + 1) % 5].clone();
+                if index == 4 {
+                    let tap = Tap(output, last.clone());
+                    tasks.push(Box::pin(cpu.run_async(input, tap)));
+                } else {
+                    tasks.push(Box::pin(cpu.run_async(input, output)));
+                }
+            }
+            results.push(last);
+        }
+        assert_eq!(tasks.len(), 600);
+
+        assert!(run_all(tasks).iter().all(Result::is_ok));
+        assert_eq!(results.iter().map(|last| last.get()).max(), Some(1_336_480));
+    }
+}
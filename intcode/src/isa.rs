@@ -0,0 +1,102 @@
+use crate::word::Word;
+use crate::{DecodeOpcodeError, Mode, Opcode};
+
+// the instruction set grew with the puzzles, a profile restricts the cpu to
+// the opcodes of the day that introduced them
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Isa {
+    // add, mul and halt, without parameter modes
+    Day2,
+    // no relative mode and no relative base
+    Day5,
+    #[default]
+    Full,
+}
+
+impl Isa {
+    #[inline(always)]
+    pub(crate) fn check<W: Word>(
+        self,
+        opcode: &Opcode,
+        word: &W,
+        ip: usize,
+    ) -> Result<(), DecodeOpcodeError<W>> {
+        match self {
+            Isa::Full => Ok(()),
+            Isa::Day5 => match opcode {
+                Opcode::Base(_) => Err(DecodeOpcodeError::InvalidOpcode(word.clone(), ip)),
+                _ if opcode
+                    .modes()
+                    .iter()
+                    .any(|mode| matches!(mode, Mode::Relative)) =>
+                {
+                    Err(DecodeOpcodeError::InvalidOpcodeModeValue(word.clone(), ip))
+                }
+                _ => Ok(()),
+            },
+            Isa::Day2 => match word.to_i64() {
+                Some(1 | 2 | 99) => Ok(()),
+                _ => Err(DecodeOpcodeError::InvalidOpcode(word.clone(), ip)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Error, Run, CPU};
+
+    fn run(isa: Isa, program: &str) -> Result<Run, Error> {
+        let mut cpu = CPU::new(parse(program), 0, None);
+        cpu.set_isa(isa);
+        cpu.run()
+    }
+
+    #[test]
+    fn test_day2() {
+        assert!(matches!(
+            run(Isa::Day2, "1,0,0,0,2,0,0,0,99"),
+            Ok(Run::Halt)
+        ));
+        assert!(matches!(
+            run(Isa::Day2, "1,0,0,0,1001,0,1,0,99"),
+            Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(
+                1001, 4
+            )))
+        ));
+        assert!(matches!(
+            run(Isa::Day2, "1,0,0,0,104,1,99"),
+            Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(
+                104, 4
+            )))
+        ));
+        assert!(matches!(
+            run(Isa::Day2, "199"),
+            Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(
+                199, 0
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_day5() {
+        assert!(matches!(
+            run(Isa::Day5, "1101,1,1,5,104,0,99"),
+            Ok(Run::Output(2))
+        ));
+        assert!(matches!(
+            run(Isa::Day5, "109,1,99"),
+            Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(
+                109, 0
+            )))
+        ));
+        assert!(matches!(
+            run(Isa::Day5, "204,0,99"),
+            Err(Error::InvalidOpcode(
+                DecodeOpcodeError::InvalidOpcodeModeValue(204, 0)
+            ))
+        ));
+        assert!(matches!(run(Isa::Full, "204,0,99"), Ok(Run::Output(204))));
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+
+use crate::word::Word;
+use crate::{Memory, CPU};
+
+// what a step changed, enough to put the cpu back where it was; a step
+// writes at most one cell, the flag tells whether it existed before
+#[derive(Debug, Clone)]
+struct Entry<W> {
+    ip: usize,
+    base: W,
+    write: Option<(usize, W, bool)>,
+    input: Option<W>,
+    output: bool,
+}
+
+// an undo log of the last steps, the oldest entries are dropped once the
+// window is full
+#[derive(Debug, Clone)]
+pub struct Journal<W = Memory> {
+    entries: VecDeque<Entry<W>>,
+    pending: Option<Entry<W>>,
+    window: usize,
+    outputs: usize,
+}
+
+impl<W: Word> Journal<W> {
+    fn new(window: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            pending: None,
+            window,
+            outputs: 0,
+        }
+    }
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    // the steps that can be undone
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // the outputs produced since the journal was enabled
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    #[inline(always)]
+    pub(crate) fn begin(&mut self, ip: usize, base: &W) {
+        self.pending = Some(Entry {
+            ip,
+            base: base.clone(),
+            write: None,
+            input: None,
+            output: false,
+        });
+    }
+
+    #[inline(always)]
+    pub(crate) fn write(&mut self, index: usize, old: &W, existed: bool) {
+        if let Some(entry) = self.pending.as_mut() {
+            entry.write = Some((index, old.clone(), existed));
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn input(&mut self, value: &W) {
+        if let Some(entry) = self.pending.as_mut() {
+            entry.input = Some(value.clone());
+        }
+    }
+
+    // a step that waited for input, halted or failed changed nothing and
+    // is not recorded
+    #[inline(always)]
+    pub(crate) fn commit(&mut self, output: bool) {
+        if let Some(mut entry) = self.pending.take() {
+            if self.window == 0 {
+                return;
+            }
+            if self.entries.len() == self.window {
+                self.entries.pop_front();
+            }
+
+            entry.output = output;
+            if output {
+                self.outputs += 1;
+            }
+            self.entries.push_back(entry);
+        }
+    }
+
+    pub(crate) fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    // forgets the last step, returning the cell it overwrote
+    pub(crate) fn take_write(&mut self) -> Option<(usize, W)> {
+        self.entries
+            .pop_back()
+            .and_then(|entry| entry.write)
+            .map(|(index, old, _)| (index, old))
+    }
+}
+
+impl<W: Word> CPU<W> {
+    // records the next steps, at most window of them can be undone
+    pub fn enable_journal(&mut self, window: usize) {
+        self.journal = Some(Box::new(Journal::new(window)));
+    }
+
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+
+    pub fn journal(&self) -> Option<&Journal<W>> {
+        self.journal.as_deref()
+    }
+
+    // undoes the last recorded step, the transcript is rewound with it but
+    // the profile is not
+    pub fn step_back(&mut self) -> bool {
+        let entry = match self.journal.as_mut().and_then(|j| j.entries.pop_back()) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if let Some((index, old, existed)) = entry.write {
+            if let Some(loops) = self.loops.as_mut() {
+                loops.write(index, &self.memory[index], &old);
+            }
+            if let Some(cache) = self.cache.as_mut() {
+                cache.invalidate(index);
+            }
+            self.memory[index] = old;
+            if !existed {
+                self.memory.forget(index);
+            }
+        }
+        self.ip = entry.ip;
+        self.base = entry.base;
+        if let Some(input) = entry.input {
+            self.input.push_front(input);
+        }
+        if entry.output {
+            if let Some(journal) = self.journal.as_mut() {
+                journal.outputs -= 1;
+            }
+        }
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.step_back();
+        }
+
+        true
+    }
+
+    // steps back to the last time the instruction at ip was about to run,
+    // nothing changes if the journal does not reach that far
+    pub fn run_back_to(&mut self, ip: usize) -> bool {
+        self.rewind_while(|entry, _| entry.ip != ip)
+    }
+
+    // steps back to just before the output with this index, counted from
+    // zero since the journal was enabled
+    pub fn rewind_to_output(&mut self, index: usize) -> bool {
+        self.rewind_while(|entry, outputs| !entry.output || outputs != index + 1)
+    }
+
+    fn rewind_while<F: Fn(&Entry<W>, usize) -> bool>(&mut self, skip: F) -> bool {
+        let journal = match self.journal.as_deref() {
+            Some(journal) => journal,
+            None => return false,
+        };
+
+        let mut outputs = journal.outputs;
+        let mut steps = None;
+        for (i, entry) in journal.entries.iter().rev().enumerate() {
+            if !skip(entry, outputs) {
+                steps = Some(i + 1);
+                break;
+            }
+            if entry.output {
+                outputs -= 1;
+            }
+        }
+
+        match steps {
+            Some(steps) => {
+                for _ in 0..steps {
+                    self.step_back();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, Run, Step, CPU};
+
+    const DAY09: &str = include_str!("../../day09/data.txt");
+
+    // everything a step back must restore
+    fn state(cpu: &CPU) -> (usize, i128, usize, Vec<i128>) {
+        (
+            cpu.ip(),
+            cpu.base,
+            cpu.pending_input(),
+            cpu.input.iter().copied().collect(),
+        )
+    }
+
+    #[test]
+    fn test_forward_and_back() {
+        let mut cpu = CPU::new(parse(DAY09), 0, Some(1));
+        cpu.enable_journal(usize::MAX);
+        let start = cpu.fork();
+
+        let mut steps = 0;
+        let mut states = vec![];
+        loop {
+            states.push((state(&cpu), cpu.fork()));
+            match cpu.step().unwrap() {
+                Step::Halt => break,
+                _ => steps += 1,
+            }
+        }
+        states.pop();
+
+        assert_eq!(cpu.journal().unwrap().len(), steps);
+        while let Some((expected, copy)) = states.pop() {
+            assert!(cpu.step_back());
+            assert_eq!(state(&cpu), expected);
+            assert!(cpu.diff(&copy).is_empty());
+            assert_eq!(cpu.memory.segments(), copy.memory.segments());
+        }
+        assert!(!cpu.step_back());
+        assert_eq!(state(&cpu), state(&start));
+
+        // and forward again to the same result
+        assert!(matches!(cpu.run(), Ok(Run::Output(2457252183))));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut cpu = CPU::new(parse("1001,5,1,5,1105,1,0"), 0, None);
+        cpu.enable_journal(3);
+
+        for _ in 0..10 {
+            cpu.step().unwrap();
+        }
+
+        assert_eq!(cpu.journal().unwrap().len(), 3);
+        assert!(cpu.step_back() && cpu.step_back() && cpu.step_back());
+        assert!(!cpu.step_back());
+        assert_eq!(cpu.ip(), 4);
+        assert_eq!(cpu.memory(5), 5);
+    }
+
+    #[test]
+    fn test_run_back_to() {
+        // counts down from 3, outputting each value
+        let mut cpu = CPU::new(parse("4,12,1001,12,-1,12,1005,12,0,99,0,0,3"), 0, None);
+        cpu.enable_journal(100);
+
+        let mut output = vec![];
+        while let Run::Output(value) = cpu.run().unwrap() {
+            output.push(value);
+        }
+        assert_eq!(output, vec![3, 2, 1]);
+
+        assert!(!cpu.run_back_to(1));
+        assert!(cpu.run_back_to(2));
+        assert_eq!((cpu.ip(), cpu.memory(12)), (2, 1));
+
+        assert!(cpu.rewind_to_output(1));
+        assert_eq!(cpu.journal().unwrap().outputs(), 1);
+        assert!(matches!(cpu.run(), Ok(Run::Output(2))));
+
+        assert!(cpu.rewind_to_output(0));
+        assert_eq!((cpu.ip(), cpu.memory(12)), (0, 3));
+        assert!(!cpu.rewind_to_output(0));
+    }
+
+    #[test]
+    fn test_input_is_given_back() {
+        let mut cpu = CPU::new(parse("3,0,3,1,99"), 0, None);
+        cpu.enable_journal(10);
+        cpu.extend_input(vec![7, 8]);
+
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+        assert_eq!(cpu.peek_range(0..2), vec![7, 8]);
+
+        assert!(cpu.run_back_to(0));
+        assert_eq!(cpu.peek_range(0..2), vec![3, 0]);
+        assert_eq!(cpu.input(), Some(7));
+        assert_eq!(cpu.pending_input(), 2);
+    }
+}
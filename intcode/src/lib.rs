@@ -1,9 +1,46 @@
-use std::collections::HashMap;
-use std::ops::{Deref, Index, IndexMut};
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::ops::Range;
 use std::str::FromStr;
 use std::sync::mpsc::{Receiver, RecvError, SendError, Sender};
 use std::thread;
 
+// the translated test fixture refers to the crate by name
+#[cfg(test)]
+extern crate self as intcode;
+
+pub mod analysis;
+pub mod ascii;
+pub mod asm;
+pub mod budget;
+mod cache;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+pub mod future;
+pub mod isa;
+pub mod journal;
+mod memory;
+pub mod patch;
+pub mod profile;
+pub mod program;
+pub mod scheduler;
+pub mod snapshot;
+pub mod transcript;
+pub mod translate;
+pub mod word;
+
+use budget::LoopDetector;
+use cache::DecodeCache;
+use isa::Isa;
+use journal::Journal;
+use memory::MemoryData;
+use patch::Change;
+use profile::Profile;
+use transcript::Transcript;
+use word::{Overflow, Word};
+
 pub type Memory = i128;
 
 const SEPARATOR: char = ',';
@@ -15,6 +52,16 @@ pub enum Mode {
     Relative,
 }
 
+impl Mode {
+    fn code(self) -> Memory {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
 enum ModeIndex {
     One,
     Two,
@@ -36,29 +83,36 @@ pub enum Opcode {
 }
 
 #[derive(Debug)]
-pub enum DecodeOpcodeError {
-    InvalidOpcode(Memory, usize),
-    InvalidOpcodeModeValue(Memory, usize),
+pub enum DecodeOpcodeError<W = Memory> {
+    InvalidOpcode(W, usize),
+    InvalidOpcodeModeValue(W, usize),
 }
 
 impl Opcode {
-    fn from(memory: &Memory, ip: usize) -> Result<Opcode, DecodeOpcodeError> {
+    pub(crate) fn from<W: Word>(memory: &W, ip: usize) -> Result<Opcode, DecodeOpcodeError<W>> {
+        let value = memory
+            .to_i64()
+            .ok_or_else(|| DecodeOpcodeError::InvalidOpcode(memory.clone(), ip))?;
+
         let mode = |index| {
-            let value = match index {
-                ModeIndex::One => memory / 100 % 10,
-                ModeIndex::Two => memory / 1000 % 10,
-                ModeIndex::Three => memory / 10000 % 10,
+            let mode = match index {
+                ModeIndex::One => value / 100 % 10,
+                ModeIndex::Two => value / 1000 % 10,
+                ModeIndex::Three => value / 10000 % 10,
             };
 
-            match value {
+            match mode {
                 0 => Ok(Mode::Position),
                 1 => Ok(Mode::Immediate),
                 2 => Ok(Mode::Relative),
-                _ => Err(DecodeOpcodeError::InvalidOpcodeModeValue(*memory, ip)),
+                _ => Err(DecodeOpcodeError::InvalidOpcodeModeValue(
+                    memory.clone(),
+                    ip,
+                )),
             }
         };
 
-        match memory % 100 {
+        match value % 100 {
             1 => Ok(Opcode::Add(
                 mode(ModeIndex::One)?,
                 mode(ModeIndex::Two)?,
@@ -85,16 +139,55 @@ impl Opcode {
             )),
             9 => Ok(Opcode::Base(mode(ModeIndex::One)?)),
             99 => Ok(Opcode::Halt),
-            _ => Err(DecodeOpcodeError::InvalidOpcode(*memory, ip)),
+            _ => Err(DecodeOpcodeError::InvalidOpcode(memory.clone(), ip)),
+        }
+    }
+
+    pub(crate) fn code(&self) -> Memory {
+        match self {
+            Opcode::Add(..) => 1,
+            Opcode::Mul(..) => 2,
+            Opcode::Input(..) => 3,
+            Opcode::Output(..) => 4,
+            Opcode::IfNEq(..) => 5,
+            Opcode::IfEq(..) => 6,
+            Opcode::IfLess(..) => 7,
+            Opcode::IfEqTo(..) => 8,
+            Opcode::Base(..) => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    pub fn modes(&self) -> Vec<Mode> {
+        match *self {
+            Opcode::Add(mode1, mode2, mode3)
+            | Opcode::Mul(mode1, mode2, mode3)
+            | Opcode::IfLess(mode1, mode2, mode3)
+            | Opcode::IfEqTo(mode1, mode2, mode3) => vec![mode1, mode2, mode3],
+            Opcode::IfNEq(mode1, mode2) | Opcode::IfEq(mode1, mode2) => vec![mode1, mode2],
+            Opcode::Input(mode1) | Opcode::Output(mode1) | Opcode::Base(mode1) => vec![mode1],
+            Opcode::Halt => vec![],
         }
     }
+
+    pub(crate) fn encode(&self) -> Memory {
+        self.modes()
+            .into_iter()
+            .zip([100, 1000, 10000])
+            .fold(self.code(), |acc, (mode, weight)| {
+                acc + mode.code() * weight
+            })
+    }
 }
 
 #[derive(Debug)]
-pub enum Error {
-    InvalidOpcode(DecodeOpcodeError),
+pub enum Error<W = Memory> {
+    InvalidOpcode(DecodeOpcodeError<W>),
     InvalidOpcodeMode(Opcode, Mode, usize),
-    InvalidOpcodeModeValue(Memory, usize),
+    InvalidOpcodeModeValue(W, usize),
+    NegativeAddress(Opcode, W, usize),
+    AddressOutOfRange(Opcode, W, usize),
+    Overflow(Opcode, usize),
     EOF,
 }
 
@@ -105,78 +198,247 @@ pub enum ErrorSpawn {
     Send(SendError<Memory>),
 }
 
+impl fmt::Display for Mode {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Mode::Position => write!(fmt, "position"),
+            Mode::Immediate => write!(fmt, "immediate"),
+            Mode::Relative => write!(fmt, "relative"),
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for DecodeOpcodeError<W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            DecodeOpcodeError::InvalidOpcode(value, ip) => {
+                write!(fmt, "invalid opcode {} at ip {}", value, ip)
+            }
+            DecodeOpcodeError::InvalidOpcodeModeValue(value, ip) => {
+                write!(fmt, "invalid parameter mode in {} at ip {}", value, ip)
+            }
+        }
+    }
+}
+
+impl<W: Word> error::Error for DecodeOpcodeError<W> {}
+
+impl<W: Word> fmt::Display for Error<W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Error::InvalidOpcode(e) => write!(fmt, "{}", e),
+            Error::InvalidOpcodeMode(opcode, mode, ip) => write!(
+                fmt,
+                "{} cannot write to a parameter in {} mode at ip {}",
+                disasm::mnemonic(opcode),
+                mode,
+                ip
+            ),
+            Error::InvalidOpcodeModeValue(value, ip) => {
+                write!(fmt, "invalid parameter mode in {} at ip {}", value, ip)
+            }
+            Error::NegativeAddress(opcode, address, ip) => write!(
+                fmt,
+                "{} accessed negative address {} at ip {}",
+                disasm::mnemonic(opcode),
+                address,
+                ip
+            ),
+            Error::AddressOutOfRange(opcode, address, ip) => write!(
+                fmt,
+                "{} accessed out of range address {} at ip {}",
+                disasm::mnemonic(opcode),
+                address,
+                ip
+            ),
+            Error::Overflow(opcode, ip) => {
+                write!(fmt, "{} overflowed at ip {}", disasm::mnemonic(opcode), ip)
+            }
+            Error::EOF => write!(fmt, "ip past the end of memory"),
+        }
+    }
+}
+
+impl<W: Word> error::Error for Error<W> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::InvalidOpcode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorSpawn {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ErrorSpawn::CPU(e) => write!(fmt, "cpu error: {}", e),
+            ErrorSpawn::Recv(e) => write!(fmt, "input channel error: {}", e),
+            ErrorSpawn::Send(e) => write!(fmt, "output channel error: {}", e),
+        }
+    }
+}
+
+impl error::Error for ErrorSpawn {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ErrorSpawn::CPU(e) => Some(e),
+            ErrorSpawn::Recv(e) => Some(e),
+            ErrorSpawn::Send(e) => Some(e),
+        }
+    }
+}
+
 #[derive(Debug)]
-pub enum Step {
+pub enum Step<W = Memory> {
     Continue,
     NeedInput,
-    Output(Memory),
+    Output(W),
     Halt,
 }
 
 #[derive(Debug)]
-pub enum Run {
+pub enum Run<W = Memory> {
     NeedInput,
-    Output(Memory),
+    Output(W),
     Halt,
 }
 
-pub struct CPU {
-    memory: MemoryData,
+pub struct CPU<W = Memory> {
+    memory: MemoryData<W>,
     ip: usize,
-    base: Memory,
-    input: Option<Memory>,
+    base: W,
+    input: VecDeque<W>,
+    overflow: Overflow,
+    isa: Isa,
+    profile: Option<Box<Profile>>,
+    loops: Option<Box<LoopDetector<W>>>,
+    journal: Option<Box<Journal<W>>>,
+    cache: Option<Box<DecodeCache>>,
+    transcript: Option<Box<Transcript<W>>>,
 }
 
-#[derive(Clone)]
-struct MemoryData(HashMap<usize, Memory>);
-
-impl MemoryData {
-    fn new(data: Vec<Memory>) -> Self {
-        Self(data.into_iter().enumerate().collect())
+impl CPU {
+    pub fn new(memory: Vec<Memory>, ip: usize, input: Option<Memory>) -> Self {
+        Self::from_words(memory, ip, input)
     }
 }
 
-impl Deref for MemoryData {
-    type Target = HashMap<usize, Memory>;
+impl<W: Word> CPU<W> {
+    // like new, for any word size
+    pub fn from_words(memory: Vec<W>, ip: usize, input: Option<W>) -> Self {
+        Self {
+            memory: MemoryData::new(memory),
+            ip,
+            base: W::default(),
+            input: input.into_iter().collect(),
+            overflow: Overflow::default(),
+            isa: Isa::default(),
+            profile: None,
+            loops: None,
+            journal: None,
+            cache: None,
+            transcript: None,
+        }
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
 
-    fn deref(&self) -> &HashMap<usize, Memory> {
-        &self.0
+    pub fn memory(&self, address: usize) -> W {
+        self.memory[address].clone()
     }
-}
 
-impl Index<usize> for MemoryData {
-    type Output = Memory;
+    pub fn peek_range(&self, addresses: Range<usize>) -> Vec<W> {
+        addresses.map(|address| self.memory(address)).collect()
+    }
+
+    // writes like the program would, so the memory grows as needed
+    pub fn poke(&mut self, address: usize, value: W) {
+        self.store(address, value);
+    }
 
-    fn index(&self, idx: usize) -> &Memory {
-        match self.0.get(&idx) {
-            Some(value) => value,
-            None => &0,
+    pub fn poke_range<I: IntoIterator<Item = W>>(&mut self, address: usize, values: I) {
+        for (offset, value) in values.into_iter().enumerate() {
+            self.poke(address + offset, value);
         }
     }
-}
 
-impl IndexMut<usize> for MemoryData {
-    fn index_mut(&mut self, idx: usize) -> &mut Memory {
-        self.0.entry(idx).or_insert(0)
+    // the cells where the other cpu memory differs from this one
+    pub fn diff(&self, other: &CPU<W>) -> Vec<Change<W>> {
+        self.memory.diff(&other.memory)
     }
-}
 
-impl CPU {
-    pub fn new(memory: Vec<Memory>, ip: usize, input: Option<Memory>) -> Self {
-        Self {
-            memory: MemoryData::new(memory),
-            ip,
-            base: 0,
-            input,
+    pub fn isa(&self) -> Isa {
+        self.isa
+    }
+
+    pub fn set_isa(&mut self, isa: Isa) {
+        self.isa = isa;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.clear();
         }
     }
 
-    pub fn input(&self) -> Option<Memory> {
-        self.input
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    // the next value the program will read, if any
+    pub fn input(&self) -> Option<W> {
+        self.input.front().cloned()
+    }
+
+    pub fn pending_input(&self) -> usize {
+        self.input.len()
     }
 
-    pub fn set_input(&mut self, input: Option<Memory>) {
-        self.input = input;
+    // replaces the pending input
+    pub fn set_input(&mut self, input: Option<W>) {
+        self.input.clear();
+        self.input.extend(input);
+    }
+
+    pub fn push_input(&mut self, input: W) {
+        self.input.push_back(input);
+    }
+
+    pub fn extend_input<I: IntoIterator<Item = W>>(&mut self, input: I) {
+        self.input.extend(input);
+    }
+
+    pub fn clear_input(&mut self) {
+        self.input.clear();
+    }
+
+    pub fn enable_profile(&mut self) {
+        self.profile.get_or_insert_with(Box::default);
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take().map(|profile| *profile)
+    }
+
+    #[inline(always)]
+    fn store(&mut self, index: usize, value: W) {
+        if let Some(loops) = self.loops.as_mut() {
+            loops.write(index, &self.memory[index], &value);
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.write(index, &self.memory[index], self.memory.contains(index));
+        }
+        if let Some(cache) = self.cache.as_mut() {
+            cache.invalidate(index);
+        }
+        self.memory[index] = value;
     }
 
     #[inline(always)]
@@ -185,19 +447,19 @@ impl CPU {
         opcode: Opcode,
         index: usize,
         mode: Mode,
-        value: Memory,
-    ) -> Result<(), Error> {
+        value: W,
+    ) -> Result<(), Error<W>> {
         match mode {
             Mode::Position => {
-                let idx = self.memory[index] as usize;
-                self.memory[idx] = value;
+                let idx = self.address(opcode, &self.memory[index])?;
+                self.store(idx, value);
 
                 Ok(())
             }
             Mode::Immediate => Err(Error::InvalidOpcodeMode(opcode, mode, self.ip)),
             Mode::Relative => {
-                let idx = (self.base + self.memory[index]) as usize;
-                self.memory[idx] = value;
+                let idx = self.address(opcode, &self.relative(opcode, &self.memory[index])?)?;
+                self.store(idx, value);
 
                 Ok(())
             }
@@ -205,46 +467,116 @@ impl CPU {
     }
 
     #[inline(always)]
-    fn read(&self, index: usize, mode: Mode) -> Result<Memory, Error> {
+    fn read(&self, opcode: Opcode, index: usize, mode: Mode) -> Result<W, Error<W>> {
         let memory = &self.memory;
         match mode {
-            Mode::Position => Ok(memory[memory[index] as usize]),
-            Mode::Immediate => Ok(memory[index]),
-            Mode::Relative => Ok(memory[(memory[index] + self.base) as usize]),
+            Mode::Position => Ok(memory[self.address(opcode, &memory[index])?].clone()),
+            Mode::Immediate => Ok(memory[index].clone()),
+            Mode::Relative => {
+                Ok(memory[self.address(opcode, &self.relative(opcode, &memory[index])?)?].clone())
+            }
         }
     }
 
-    pub fn step(&mut self) -> Result<Step, Error> {
-        let opcode = Opcode::from(self.memory.get(&self.ip).ok_or(Error::EOF)?, self.ip)
+    #[inline(always)]
+    fn relative(&self, opcode: Opcode, offset: &W) -> Result<W, Error<W>> {
+        self.base
+            .sum(offset, self.overflow)
+            .ok_or(Error::Overflow(opcode, self.ip))
+    }
+
+    #[inline(always)]
+    fn address(&self, opcode: Opcode, address: &W) -> Result<usize, Error<W>> {
+        address.to_usize().ok_or_else(|| {
+            if address.is_negative() {
+                Error::NegativeAddress(opcode, address.clone(), self.ip)
+            } else {
+                Error::AddressOutOfRange(opcode, address.clone(), self.ip)
+            }
+        })
+    }
+
+    pub fn step(&mut self) -> Result<Step<W>, Error<W>> {
+        let ip = self.ip;
+        let opcode = self.decode(ip)?;
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal.begin(ip, &self.base);
+        }
+
+        let step = self.execute(opcode)?;
+
+        if let Some(journal) = self.journal.as_mut() {
+            match step {
+                Step::Continue => journal.commit(false),
+                Step::Output(_) => journal.commit(true),
+                Step::NeedInput | Step::Halt => journal.cancel(),
+            }
+        }
+
+        if let Some(profile) = self.profile.as_mut() {
+            if !matches!(step, Step::NeedInput) {
+                profile.record(ip, &opcode, self.ip);
+            }
+        }
+
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.step(&step);
+        }
+
+        Ok(step)
+    }
+
+    #[inline(always)]
+    fn decode(&mut self, ip: usize) -> Result<Opcode, Error<W>> {
+        let word = self.memory.get(ip).ok_or(Error::EOF)?;
+        if let Some(opcode) = self.cache.as_ref().and_then(|cache| cache.get(ip)) {
+            return Ok(opcode);
+        }
+
+        let opcode = Opcode::from(word, ip)
+            .and_then(|opcode| self.isa.check(&opcode, word, ip).map(|_| opcode))
             .map_err(Error::InvalidOpcode)?;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.insert(ip, opcode);
+        }
+
+        Ok(opcode)
+    }
 
+    #[inline(always)]
+    fn execute(&mut self, opcode: Opcode) -> Result<Step<W>, Error<W>> {
         match opcode {
             Opcode::Add(mode1, mode2, mode3) => {
-                self.write(
-                    opcode,
-                    self.ip + 3,
-                    mode3,
-                    self.read(self.ip + 1, mode1)? + self.read(self.ip + 2, mode2)?,
-                )?;
+                let value = self
+                    .read(opcode, self.ip + 1, mode1)?
+                    .sum(&self.read(opcode, self.ip + 2, mode2)?, self.overflow)
+                    .ok_or(Error::Overflow(opcode, self.ip))?;
+                self.write(opcode, self.ip + 3, mode3, value)?;
                 self.ip += 4;
 
                 Ok(Step::Continue)
             }
             Opcode::Mul(mode1, mode2, mode3) => {
-                self.write(
-                    opcode,
-                    self.ip + 3,
-                    mode3,
-                    self.read(self.ip + 1, mode1)? * self.read(self.ip + 2, mode2)?,
-                )?;
+                let value = self
+                    .read(opcode, self.ip + 1, mode1)?
+                    .product(&self.read(opcode, self.ip + 2, mode2)?, self.overflow)
+                    .ok_or(Error::Overflow(opcode, self.ip))?;
+                self.write(opcode, self.ip + 3, mode3, value)?;
                 self.ip += 4;
 
                 Ok(Step::Continue)
             }
             Opcode::Input(mode1) => {
-                if let Some(input) = self.input {
-                    self.write(opcode, self.ip + 1, mode1, input)?;
-                    self.input = None;
+                if let Some(input) = self.input.front() {
+                    if let Some(journal) = self.journal.as_mut() {
+                        journal.input(input);
+                    }
+                    self.write(opcode, self.ip + 1, mode1, input.clone())?;
+                    let input = self.input.pop_front();
+                    if let (Some(transcript), Some(input)) = (self.transcript.as_mut(), input) {
+                        transcript.input(input);
+                    }
                     self.ip += 2;
 
                     Ok(Step::Continue)
@@ -253,14 +585,14 @@ impl CPU {
                 }
             }
             Opcode::Output(mode1) => {
-                let output = self.read(self.ip + 1, mode1)?;
+                let output = self.read(opcode, self.ip + 1, mode1)?;
                 self.ip += 2;
 
                 Ok(Step::Output(output))
             }
             Opcode::IfNEq(mode1, mode2) => {
-                self.ip = if self.read(self.ip + 1, mode1)? != 0 {
-                    self.read(self.ip + 2, mode2)? as usize
+                self.ip = if !self.read(opcode, self.ip + 1, mode1)?.is_zero() {
+                    self.address(opcode, &self.read(opcode, self.ip + 2, mode2)?)?
                 } else {
                     self.ip + 3
                 };
@@ -268,8 +600,8 @@ impl CPU {
                 Ok(Step::Continue)
             }
             Opcode::IfEq(mode1, mode2) => {
-                self.ip = if self.read(self.ip + 1, mode1)? == 0 {
-                    self.read(self.ip + 2, mode2)? as usize
+                self.ip = if self.read(opcode, self.ip + 1, mode1)?.is_zero() {
+                    self.address(opcode, &self.read(opcode, self.ip + 2, mode2)?)?
                 } else {
                     self.ip + 3
                 };
@@ -277,10 +609,12 @@ impl CPU {
                 Ok(Step::Continue)
             }
             Opcode::IfLess(mode1, mode2, mode3) => {
-                let value = if self.read(self.ip + 1, mode1)? < self.read(self.ip + 2, mode2)? {
-                    1
+                let value = if self.read(opcode, self.ip + 1, mode1)?
+                    < self.read(opcode, self.ip + 2, mode2)?
+                {
+                    W::from(1)
                 } else {
-                    0
+                    W::from(0)
                 };
 
                 self.write(opcode, self.ip + 3, mode3, value)?;
@@ -289,10 +623,12 @@ impl CPU {
                 Ok(Step::Continue)
             }
             Opcode::IfEqTo(mode1, mode2, mode3) => {
-                let value = if self.read(self.ip + 1, mode1)? == self.read(self.ip + 2, mode2)? {
-                    1
+                let value = if self.read(opcode, self.ip + 1, mode1)?
+                    == self.read(opcode, self.ip + 2, mode2)?
+                {
+                    W::from(1)
                 } else {
-                    0
+                    W::from(0)
                 };
 
                 self.write(opcode, self.ip + 3, mode3, value)?;
@@ -301,7 +637,7 @@ impl CPU {
                 Ok(Step::Continue)
             }
             Opcode::Base(mode1) => {
-                self.base += self.read(self.ip + 1, mode1)?;
+                self.base = self.relative(opcode, &self.read(opcode, self.ip + 1, mode1)?)?;
                 self.ip += 2;
 
                 Ok(Step::Continue)
@@ -310,25 +646,47 @@ impl CPU {
         }
     }
 
-    pub fn copy_with_input(&self, input: Option<Memory>) -> Self {
+    pub fn fork(&self) -> Self {
         Self {
             memory: self.memory.clone(),
-            input,
+            input: self.input.clone(),
+            profile: self.profile.clone(),
+            loops: self.loops.clone(),
+            journal: self.journal.clone(),
+            cache: self.cache.clone(),
+            transcript: self.transcript.clone(),
+            base: self.base.clone(),
             ..*self
         }
     }
 
-    pub fn run(&mut self) -> Result<Run, Error> {
-        loop {
+    pub fn copy_with_input(&self, input: Option<W>) -> Self {
+        let mut cpu = self.fork();
+        cpu.set_input(input);
+        cpu
+    }
+
+    pub fn run(&mut self) -> Result<Run<W>, Error<W>> {
+        let start = self.profile.as_ref().map(|profile| profile.instructions());
+
+        let run = loop {
             match self.step()? {
                 Step::Continue => {}
-                Step::NeedInput => break Ok(Run::NeedInput),
-                Step::Output(value) => break Ok(Run::Output(value)),
-                Step::Halt => break Ok(Run::Halt),
+                Step::NeedInput => break Run::NeedInput,
+                Step::Output(value) => break Run::Output(value),
+                Step::Halt => break Run::Halt,
             }
+        };
+
+        if let (Some(profile), Some(start)) = (self.profile.as_mut(), start) {
+            profile.record_run(profile.instructions() - start);
         }
+
+        Ok(run)
     }
+}
 
+impl CPU {
     pub fn spawn(
         mut self,
         input_rx: Receiver<Memory>,
@@ -339,7 +697,7 @@ impl CPU {
                 match self.run().map_err(ErrorSpawn::CPU)? {
                     Run::Halt => return Ok(()),
                     Run::NeedInput => {
-                        self.input = Some(input_rx.recv().map_err(ErrorSpawn::Recv)?);
+                        self.push_input(input_rx.recv().map_err(ErrorSpawn::Recv)?);
                     }
                     Run::Output(value) => {
                         output_tx.send(value).map_err(ErrorSpawn::Send)?;
@@ -350,22 +708,16 @@ impl CPU {
     }
 }
 
-impl ToOwned for CPU {
-    type Owned = CPU;
+impl<W: Word> ToOwned for CPU<W> {
+    type Owned = CPU<W>;
 
     fn to_owned(&self) -> Self {
-        Self {
-            memory: self.memory.clone(),
-            ..*self
-        }
+        self.fork()
     }
 }
 
 pub fn parse<T: FromStr>(data: &str) -> Vec<T> {
-    data.trim()
-        .split(SEPARATOR)
-        .map(|s| s.parse().unwrap_or_else(|_| panic!("cannot parse: {}", s)))
-        .collect()
+    program::parse(data).unwrap_or_else(|e| panic!("cannot parse: {}", e))
 }
 
 #[cfg(test)]
@@ -410,7 +762,7 @@ mod tests {
 
         println!("test_test_with_input ip: {}", cpu.ip);
 
-        assert_eq!(cpu.input, None);
+        assert_eq!(cpu.input(), None);
     }
 
     #[test]
@@ -486,4 +838,233 @@ mod tests {
 
         assert_eq!(output, vec![1125899906842624]);
     }
+
+    #[test]
+    fn test_fork_is_isolated() {
+        // read a value, store it at 20, output it and loop
+        let memory = parse(r#"3,20,4,20,1105,1,0"#);
+
+        let mut cpu = CPU::new(memory, 0, Some(1));
+        assert!(matches!(cpu.run(), Ok(Run::Output(1))));
+
+        let mut fork_a = cpu.copy_with_input(Some(2));
+        let mut fork_b = cpu.copy_with_input(Some(3));
+
+        assert!(matches!(fork_a.run(), Ok(Run::Output(2))));
+        assert!(matches!(fork_b.run(), Ok(Run::Output(3))));
+
+        assert_eq!(cpu.memory[20], 1);
+        assert_eq!(fork_a.memory[20], 2);
+        assert_eq!(fork_b.memory[20], 3);
+    }
+
+    #[test]
+    fn test_fork_of_fork() {
+        let memory = parse(r#"3,20,4,20,1105,1,0"#);
+
+        let mut cpu = CPU::new(memory, 0, Some(1));
+        assert!(matches!(cpu.run(), Ok(Run::Output(1))));
+
+        let mut fork = cpu.fork();
+        assert!(matches!(fork.run(), Ok(Run::NeedInput)));
+
+        let mut fork_of_fork = fork.copy_with_input(Some(5));
+        assert!(matches!(fork_of_fork.run(), Ok(Run::Output(5))));
+
+        cpu.set_input(Some(4));
+        assert!(matches!(cpu.run(), Ok(Run::Output(4))));
+
+        assert_eq!(cpu.memory[20], 4);
+        assert_eq!(fork.memory[20], 1);
+        assert_eq!(fork_of_fork.memory[20], 5);
+        assert_eq!(fork.input(), None);
+    }
+
+    #[test]
+    fn test_input_queue() {
+        // adds the two values read and outputs the sum, forever
+        let memory = parse(r#"3,20,3,21,1,20,21,22,4,22,1105,1,0"#);
+
+        let mut cpu = CPU::new(memory, 0, None);
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+
+        cpu.push_input(1);
+        cpu.extend_input([2, 3]);
+        cpu.extend_input(vec![4, 5].into_iter().map(|value| value * 10));
+        assert_eq!(cpu.input(), Some(1));
+        assert_eq!(cpu.pending_input(), 5);
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(3))));
+        assert!(matches!(cpu.run(), Ok(Run::Output(43))));
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+        assert_eq!(cpu.input(), None);
+
+        cpu.set_input(Some(6));
+        assert_eq!(cpu.pending_input(), 1);
+        assert!(matches!(cpu.run(), Ok(Run::Output(56))));
+
+        cpu.extend_input([8, 9]);
+        cpu.clear_input();
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+    }
+
+    #[test]
+    fn test_address_faults() {
+        let run = |program: &str| CPU::new(parse(program), 0, None).run();
+
+        assert!(matches!(
+            run(r#"4,-1,99"#),
+            Err(Error::NegativeAddress(Opcode::Output(_), -1, 0))
+        ));
+        assert!(matches!(
+            run(r#"109,-5,21101,1,1,2,99"#),
+            Err(Error::NegativeAddress(Opcode::Add(..), -3, 2))
+        ));
+        assert!(matches!(
+            run(r#"1105,1,-7"#),
+            Err(Error::NegativeAddress(Opcode::IfNEq(..), -7, 0))
+        ));
+        assert!(matches!(
+            run(r#"1,1180591620717411303424,0,0,99"#),
+            Err(Error::AddressOutOfRange(
+                Opcode::Add(..),
+                1180591620717411303424,
+                0
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let run = |program: &str| CPU::new(parse(program), 0, None).run();
+
+        assert!(matches!(
+            run(r#"1101,1,1,3,11101,1,1,3"#),
+            Err(Error::InvalidOpcodeMode(
+                Opcode::Add(..),
+                Mode::Immediate,
+                4
+            ))
+        ));
+        assert!(matches!(
+            run(r#"42"#),
+            Err(Error::InvalidOpcode(DecodeOpcodeError::InvalidOpcode(
+                42, 0
+            )))
+        ));
+        assert!(matches!(
+            run(r#"301,0,0,0"#),
+            Err(Error::InvalidOpcode(
+                DecodeOpcodeError::InvalidOpcodeModeValue(301, 0)
+            ))
+        ));
+        assert!(matches!(run(r#"1101,1,1,5,1"#), Err(Error::EOF)));
+        // a far write does not bring the cells before it into existence
+        assert!(matches!(
+            run(r#"1101,7,0,1000000,1105,1,500"#),
+            Err(Error::EOF)
+        ));
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = |program: &str| -> Box<dyn error::Error> {
+            Box::new(CPU::new(parse(program), 0, None).run().unwrap_err())
+        };
+
+        assert_eq!(
+            error(r#"4,-1,99"#).to_string(),
+            "out accessed negative address -1 at ip 0"
+        );
+        assert_eq!(
+            error(r#"1,1180591620717411303424,0,0,99"#).to_string(),
+            "add accessed out of range address 1180591620717411303424 at ip 0"
+        );
+        assert_eq!(
+            error(r#"11101,1,1,3"#).to_string(),
+            "add cannot write to a parameter in immediate mode at ip 0"
+        );
+        assert_eq!(error(r#"42"#).to_string(), "invalid opcode 42 at ip 0");
+        assert!(error(r#"42"#).source().is_some());
+        assert_eq!(
+            error(r#"1101,1,1,5,1"#).to_string(),
+            "ip past the end of memory"
+        );
+
+        let spawn: Box<dyn error::Error> = Box::new(ErrorSpawn::CPU(Error::EOF));
+        assert_eq!(spawn.to_string(), "cpu error: ip past the end of memory");
+        assert!(spawn.source().is_some());
+    }
+
+    fn outputs<W: Word>(program: &str, input: &[i32]) -> Vec<String> {
+        let mut cpu = CPU::<W>::from_words(parse(program), 0, None);
+        cpu.extend_input(input.iter().map(|&value| W::from(value)));
+
+        let mut output = vec![];
+        loop {
+            match cpu.run().unwrap() {
+                Run::NeedInput => panic!("invalid input request"),
+                Run::Output(value) => output.push(value.to_string()),
+                Run::Halt => break output,
+            }
+        }
+    }
+
+    #[test]
+    fn test_word_sizes() {
+        let programs = [
+            (include_str!("../../day05/data.txt"), vec![1]),
+            (include_str!("../../day05/data.txt"), vec![5]),
+            (include_str!("../../day09/data.txt"), vec![1]),
+            (include_str!("../../day09/data.txt"), vec![2]),
+            (
+                r#"109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"#,
+                vec![],
+            ),
+            (r#"1102,34915192,34915192,7,4,7,99,0"#, vec![]),
+        ];
+
+        for (program, input) in programs {
+            let expected = outputs::<i128>(program, &input);
+            assert_eq!(outputs::<i64>(program, &input), expected);
+            #[cfg(feature = "bigint")]
+            assert_eq!(outputs::<word::BigInt>(program, &input), expected);
+        }
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        let mut cpu = CPU::new(parse(r#"1,0,0,0,99"#), 0, None);
+
+        cpu.poke_range(1, vec![5, 6]);
+        cpu.poke_range(5, vec![30, 40]);
+        cpu.poke(8, 7);
+
+        assert_eq!(cpu.peek_range(0..9), vec![1, 5, 6, 0, 99, 30, 40, 0, 7]);
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+        assert_eq!(cpu.memory(0), 70);
+    }
+
+    #[test]
+    fn test_overflow_policy() {
+        // squares its input and outputs the result
+        let program = parse::<i64>(r#"3,9,2,9,9,9,4,9,99,0"#);
+        let run = |overflow| {
+            let mut cpu = CPU::from_words(program.to_owned(), 0, Some(i64::MAX));
+            cpu.set_overflow(overflow);
+            cpu.run()
+        };
+
+        assert!(matches!(
+            run(Overflow::Trap),
+            Err(Error::Overflow(Opcode::Mul(..), 2))
+        ));
+        assert!(matches!(run(Overflow::Wrap), Ok(Run::Output(1))));
+        assert!(matches!(run(Overflow::Saturate), Ok(Run::Output(i64::MAX))));
+
+        // a relative address past the end of the word
+        let mut cpu = CPU::new(parse(r#"1201,1,0,0,99"#), 0, None);
+        cpu.base = Memory::MAX;
+        assert_eq!(cpu.run().unwrap_err().to_string(), "add overflowed at ip 0");
+    }
 }
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::patch::Change;
+use crate::word::Word;
+use crate::Memory;
+
+const PAGE_BITS: usize = 8;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+const PAGE_MASK: usize = PAGE_SIZE - 1;
+
+// pages beyond this index live in a sparse map, so a wild write to a
+// huge address does not allocate a huge page table
+const DENSE_PAGES: usize = 1 << 12;
+
+// the cells of a page and which of them were written
+#[derive(Clone)]
+struct Page<W> {
+    cells: [W; PAGE_SIZE],
+    written: [u64; PAGE_SIZE / 64],
+}
+
+// pages are shared between clones and copied on the first write, so
+// cloning a memory costs one reference count per page
+#[derive(Clone)]
+pub(crate) struct MemoryData<W = Memory> {
+    pages: Vec<Option<Arc<Page<W>>>>,
+    far_pages: HashMap<usize, Arc<Page<W>>>,
+    // the cells of the program, the cells past it exist once written
+    len: usize,
+    // read from the pages that were never written
+    zero: W,
+}
+
+impl<W: Word> MemoryData<W> {
+    pub(crate) fn new(data: Vec<W>) -> Self {
+        let mut memory = Self {
+            pages: Vec::with_capacity((data.len() + PAGE_MASK) >> PAGE_BITS),
+            far_pages: HashMap::new(),
+            len: 0,
+            zero: W::default(),
+        };
+
+        for (page, chunk) in data.chunks(PAGE_SIZE).enumerate() {
+            memory.page_mut(page).cells[..chunk.len()].clone_from_slice(chunk);
+        }
+        memory.len = data.len();
+
+        memory
+    }
+
+    // None when a segment runs past the last address; the cells of the
+    // segments past len are taken as written
+    pub(crate) fn from_segments(len: usize, segments: Vec<(usize, Vec<W>)>) -> Option<Self> {
+        let mut memory = Self::new(vec![]);
+        for (start, values) in segments {
+            start.checked_add(values.len().saturating_sub(1))?;
+            for (offset, value) in values.into_iter().enumerate() {
+                memory[start + offset] = value;
+            }
+        }
+        memory.len = len;
+
+        Some(memory)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    // the allocated cells of the program and the cells written past it, in
+    // address order; the zeros ending a run inside the program are dropped
+    pub(crate) fn segments(&self) -> Vec<(usize, Vec<W>)> {
+        let mut pages = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| page.as_ref().map(|page| (index, page)))
+            .chain(self.far_pages.iter().map(|(index, page)| (*index, page)))
+            .collect::<Vec<_>>();
+        pages.sort_by_key(|(index, _)| *index);
+
+        let mut segments: Vec<(usize, Vec<W>)> = vec![];
+        for (index, page) in pages {
+            let start = index << PAGE_BITS;
+            for (offset, value) in page.cells.iter().enumerate() {
+                let address = start + offset;
+                if address >= self.len && !page.is_written(offset) {
+                    continue;
+                }
+                match segments.last_mut() {
+                    Some((segment_start, values)) if *segment_start + values.len() == address => {
+                        values.push(value.clone())
+                    }
+                    _ => segments.push((address, vec![value.clone()])),
+                }
+            }
+        }
+
+        for (start, values) in segments.iter_mut() {
+            while values.last().is_some_and(Word::is_zero) && *start + values.len() - 1 < self.len {
+                values.pop();
+            }
+        }
+        segments.retain(|(_, values)| !values.is_empty());
+
+        segments
+    }
+
+    pub(crate) fn get(&self, idx: usize) -> Option<&W> {
+        if self.contains(idx) {
+            Some(&self[idx])
+        } else {
+            None
+        }
+    }
+
+    // a cell of the program or one written since
+    #[inline(always)]
+    pub(crate) fn contains(&self, idx: usize) -> bool {
+        idx < self.len
+            || self
+                .page(idx >> PAGE_BITS)
+                .is_some_and(|page| page.is_written(idx & PAGE_MASK))
+    }
+
+    // takes back a write to a cell that did not exist, it must already be
+    // back to zero
+    pub(crate) fn forget(&mut self, idx: usize) {
+        if idx >= self.len {
+            self.page_mut(idx >> PAGE_BITS).written[(idx & PAGE_MASK) >> 6] &= !(1 << (idx & 63));
+        }
+    }
+
+    // cells that differ from the other memory, pages still shared by the
+    // two are skipped
+    pub(crate) fn diff(&self, other: &Self) -> Vec<Change<W>> {
+        let mut pages = self
+            .allocated()
+            .chain(other.allocated())
+            .collect::<Vec<_>>();
+        pages.sort_unstable();
+        pages.dedup();
+
+        let mut changes = vec![];
+        for page in pages {
+            if let (Some(a), Some(b)) = (self.shared_page(page), other.shared_page(page)) {
+                if Arc::ptr_eq(a, b) {
+                    continue;
+                }
+            }
+
+            let start = page << PAGE_BITS;
+            for address in start..=start + PAGE_MASK {
+                if self[address] != other[address] {
+                    changes.push(Change {
+                        address,
+                        old: self[address].clone(),
+                        new: other[address].clone(),
+                    });
+                }
+            }
+        }
+
+        changes
+    }
+
+    fn allocated(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| page.as_ref().map(|_| index))
+            .chain(self.far_pages.keys().copied())
+    }
+
+    #[inline(always)]
+    fn shared_page(&self, page: usize) -> Option<&Arc<Page<W>>> {
+        if page < DENSE_PAGES {
+            self.pages.get(page).and_then(Option::as_ref)
+        } else {
+            self.far_pages.get(&page)
+        }
+    }
+
+    #[inline(always)]
+    fn page(&self, page: usize) -> Option<&Page<W>> {
+        self.shared_page(page).map(Arc::as_ref)
+    }
+
+    #[inline(always)]
+    fn page_mut(&mut self, page: usize) -> &mut Page<W> {
+        if page < DENSE_PAGES {
+            if page >= self.pages.len() {
+                self.pages.resize_with(page + 1, || None);
+            }
+            Arc::make_mut(self.pages[page].get_or_insert_with(zero_page))
+        } else {
+            Arc::make_mut(self.far_pages.entry(page).or_insert_with(zero_page))
+        }
+    }
+}
+
+impl<W> Page<W> {
+    #[inline(always)]
+    fn is_written(&self, offset: usize) -> bool {
+        self.written[offset >> 6] & (1 << (offset & 63)) != 0
+    }
+}
+
+fn zero_page<W: Word>() -> Arc<Page<W>> {
+    Arc::new(Page {
+        cells: std::array::from_fn(|_| W::default()),
+        written: [0; PAGE_SIZE / 64],
+    })
+}
+
+impl<W: Word> Index<usize> for MemoryData<W> {
+    type Output = W;
+
+    #[inline(always)]
+    fn index(&self, idx: usize) -> &W {
+        match self.page(idx >> PAGE_BITS) {
+            Some(page) => &page.cells[idx & PAGE_MASK],
+            None => &self.zero,
+        }
+    }
+}
+
+impl<W: Word> IndexMut<usize> for MemoryData<W> {
+    #[inline(always)]
+    fn index_mut(&mut self, idx: usize) -> &mut W {
+        let page = self.page_mut(idx >> PAGE_BITS);
+        page.written[(idx & PAGE_MASK) >> 6] |= 1 << (idx & 63);
+        &mut page.cells[idx & PAGE_MASK]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_filled() {
+        let memory = MemoryData::<Memory>::new(vec![1, 2, 3]);
+
+        assert_eq!(memory[1], 2);
+        assert_eq!(memory[3], 0);
+        assert_eq!(memory[PAGE_SIZE * 10], 0);
+        assert_eq!(memory[usize::MAX], 0);
+        assert_eq!(memory.len, 3);
+    }
+
+    #[test]
+    fn test_write_grows() {
+        let mut memory = MemoryData::<Memory>::new(vec![1, 2, 3]);
+
+        memory[PAGE_SIZE + 5] = 42;
+
+        assert_eq!(memory[PAGE_SIZE + 5], 42);
+        assert_eq!(memory[PAGE_SIZE + 4], 0);
+        assert_eq!(memory.len, 3);
+        assert_eq!(memory.get(PAGE_SIZE + 5), Some(&42));
+        // the cells between the program and the write still do not exist
+        assert_eq!(memory.get(PAGE_SIZE + 4), None);
+        assert_eq!(memory.get(3), None);
+        assert_eq!(memory.get(PAGE_SIZE + 6), None);
+
+        memory[PAGE_SIZE + 5] = 0;
+        memory.forget(PAGE_SIZE + 5);
+
+        assert_eq!(memory.get(PAGE_SIZE + 5), None);
+    }
+
+    #[test]
+    fn test_far_write() {
+        let mut memory = MemoryData::<Memory>::new(vec![]);
+
+        memory[usize::MAX - 1] = 7;
+
+        assert_eq!(memory[usize::MAX - 1], 7);
+        assert_eq!(memory[usize::MAX], 0);
+        assert!(memory.pages.is_empty());
+    }
+
+    #[test]
+    fn test_clone_is_independent() {
+        let mut memory = MemoryData::<Memory>::new(vec![1, 2, 3]);
+        let copy = memory.clone();
+
+        memory[0] = 10;
+
+        assert_eq!(memory[0], 10);
+        assert_eq!(copy[0], 1);
+    }
+
+    #[test]
+    fn test_clone_shares_pages() {
+        let mut memory = MemoryData::<Memory>::new(vec![0; PAGE_SIZE * 2]);
+        let copy = memory.clone();
+
+        memory[PAGE_SIZE] = 1;
+
+        let shared = |page: usize| {
+            Arc::ptr_eq(
+                memory.pages[page].as_ref().unwrap(),
+                copy.pages[page].as_ref().unwrap(),
+            )
+        };
+
+        assert!(shared(0));
+        assert!(!shared(1));
+    }
+
+    #[test]
+    fn test_segments() {
+        let mut memory = MemoryData::<Memory>::new(vec![1, 2, 3]);
+        memory[PAGE_SIZE + 1] = 4;
+        memory[PAGE_SIZE * 3] = 5;
+        memory[usize::MAX - 1] = 6;
+
+        memory[PAGE_SIZE * 3 + 1] = 0;
+
+        let segments = memory.segments();
+
+        assert_eq!(
+            segments,
+            vec![
+                (0, vec![1, 2, 3]),
+                (PAGE_SIZE + 1, vec![4]),
+                (PAGE_SIZE * 3, vec![5, 0]),
+                (usize::MAX - 1, vec![6]),
+            ]
+        );
+
+        let copy = MemoryData::from_segments(memory.len, segments).unwrap();
+
+        assert_eq!(copy.len, memory.len);
+        assert_eq!(copy.segments(), memory.segments());
+        assert_eq!(copy.get(PAGE_SIZE * 3 + 1), Some(&0));
+        assert_eq!(copy.get(PAGE_SIZE * 3 + 2), None);
+
+        assert!(MemoryData::<Memory>::from_segments(0, vec![(usize::MAX, vec![1])]).is_some());
+        assert!(MemoryData::<Memory>::from_segments(0, vec![(usize::MAX, vec![1, 2])]).is_none());
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut memory = MemoryData::<Memory>::new(vec![1; PAGE_SIZE * 2]);
+        let copy = memory.clone();
+
+        memory[PAGE_SIZE + 1] = 2;
+        memory[usize::MAX - 1] = 3;
+
+        assert_eq!(
+            copy.diff(&memory),
+            vec![
+                Change {
+                    address: PAGE_SIZE + 1,
+                    old: 1,
+                    new: 2
+                },
+                Change {
+                    address: usize::MAX - 1,
+                    old: 0,
+                    new: 3
+                },
+            ]
+        );
+        assert!(memory.diff(&memory.clone()).is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::iter::FromIterator;
+use std::str::FromStr;
+
+use crate::word::Word;
+use crate::{Memory, CPU, SEPARATOR};
+
+// the cells apply can add past the end of a program
+const MAX_GROWTH: usize = 1 << 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    MissingValue(String),
+    InvalidAddress(String),
+    InvalidValue(String),
+    AddressOutOfRange(usize),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            PatchError::MissingValue(entry) => write!(fmt, "missing value in {:?}", entry),
+            PatchError::InvalidAddress(token) => write!(fmt, "invalid address {:?}", token),
+            PatchError::InvalidValue(token) => write!(fmt, "invalid value {:?}", token),
+            PatchError::AddressOutOfRange(address) => {
+                write!(fmt, "address {} out of range", address)
+            }
+        }
+    }
+}
+
+impl error::Error for PatchError {}
+
+// a cell that differs between two memories
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<W = Memory> {
+    pub address: usize,
+    pub old: W,
+    pub new: W,
+}
+
+// values to write over a program before it runs, the last value for an
+// address wins; the text form is a list of address=value entries, where
+// value may be a comma separated run of consecutive cells: "0=2 1=12,2"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch<W = Memory> {
+    cells: BTreeMap<usize, W>,
+}
+
+impl<W: Word> Patch<W> {
+    pub fn new() -> Self {
+        Self {
+            cells: BTreeMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, address: usize, value: W) {
+        self.cells.insert(address, value);
+    }
+
+    // nothing is set when the values do not fit below the last address
+    pub fn set_range<I: IntoIterator<Item = W>>(
+        &mut self,
+        address: usize,
+        values: I,
+    ) -> Result<(), PatchError> {
+        let values = values.into_iter().collect::<Vec<_>>();
+        address
+            .checked_add(values.len().saturating_sub(1))
+            .ok_or(PatchError::AddressOutOfRange(address))?;
+        for (offset, value) in values.into_iter().enumerate() {
+            self.set(address + offset, value);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, address: usize) -> Option<&W> {
+        self.cells.get(&address)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &W)> {
+        self.cells.iter().map(|(&address, value)| (address, value))
+    }
+
+    // consecutive cells merged into runs
+    pub fn runs(&self) -> Vec<(usize, Vec<W>)> {
+        let mut runs: Vec<(usize, Vec<W>)> = vec![];
+        for (address, value) in self.iter() {
+            match runs.last_mut() {
+                Some((start, values)) if *start + values.len() == address => {
+                    values.push(value.clone())
+                }
+                _ => runs.push((address, vec![value.clone()])),
+            }
+        }
+        runs
+    }
+
+    // the patch that turns the old side of the changes into the new one
+    pub fn from_changes(changes: &[Change<W>]) -> Self {
+        changes
+            .iter()
+            .map(|change| (change.address, change.new.clone()))
+            .collect()
+    }
+
+    pub fn merge(&mut self, other: &Patch<W>) {
+        for (address, value) in other.iter() {
+            self.set(address, value.clone());
+        }
+    }
+
+    // applies the patch to a plain program, growing it when needed; nothing
+    // is applied when a cell is too far past the end of the program
+    pub fn apply(&self, program: &mut Vec<W>) -> Result<(), PatchError> {
+        if let Some(&address) = self.cells.keys().next_back() {
+            if address >= program.len().saturating_add(MAX_GROWTH) {
+                return Err(PatchError::AddressOutOfRange(address));
+            }
+        }
+
+        for (address, value) in self.iter() {
+            if address >= program.len() {
+                program.resize(address + 1, W::default());
+            }
+            program[address] = value.clone();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Word> Default for Patch<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Word> FromIterator<(usize, W)> for Patch<W> {
+    fn from_iter<I: IntoIterator<Item = (usize, W)>>(iter: I) -> Self {
+        let mut patch = Self::new();
+        for (address, value) in iter {
+            patch.set(address, value);
+        }
+        patch
+    }
+}
+
+impl<W: Word> FromStr for Patch<W> {
+    type Err = PatchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patch = Self::new();
+        for entry in s.split_whitespace() {
+            let (address, values) = entry
+                .split_once('=')
+                .ok_or_else(|| PatchError::MissingValue(entry.to_string()))?;
+            let address = address
+                .parse()
+                .map_err(|_| PatchError::InvalidAddress(address.to_string()))?;
+            if values.is_empty() {
+                return Err(PatchError::MissingValue(entry.to_string()));
+            }
+            let values = values
+                .split(SEPARATOR)
+                .map(|value| {
+                    value
+                        .parse()
+                        .map_err(|_| PatchError::InvalidValue(value.to_string()))
+                })
+                .collect::<Result<Vec<W>, _>>()?;
+            patch.set_range(address, values)?;
+        }
+        Ok(patch)
+    }
+}
+
+impl<W: Word> fmt::Display for Patch<W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        for (i, (address, values)) in self.runs().into_iter().enumerate() {
+            if i > 0 {
+                write!(fmt, " ")?;
+            }
+            write!(fmt, "{}=", address)?;
+            for (j, value) in values.iter().enumerate() {
+                if j > 0 {
+                    write!(fmt, "{}", SEPARATOR)?;
+                }
+                write!(fmt, "{}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Word> CPU<W> {
+    pub fn patch(&mut self, patch: &Patch<W>) {
+        for (address, value) in patch.iter() {
+            self.poke(address, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Run};
+
+    #[test]
+    fn test_parse() {
+        let patch = "0=2 1=12,2\n10=-5".parse::<Patch>().unwrap();
+
+        assert_eq!(patch.len(), 4);
+        assert_eq!(patch.get(0), Some(&2));
+        assert_eq!(patch.get(2), Some(&2));
+        assert_eq!(patch.get(10), Some(&-5));
+        assert_eq!(patch.to_string(), "0=2,12,2 10=-5");
+        assert_eq!(patch.to_string().parse::<Patch>(), Ok(patch));
+
+        assert_eq!("".parse::<Patch>(), Ok(Patch::new()));
+        assert_eq!(
+            "0".parse::<Patch>(),
+            Err(PatchError::MissingValue("0".to_string()))
+        );
+        assert_eq!(
+            "0=".parse::<Patch>(),
+            Err(PatchError::MissingValue("0=".to_string()))
+        );
+        assert_eq!(
+            "-1=2".parse::<Patch>(),
+            Err(PatchError::InvalidAddress("-1".to_string()))
+        );
+        assert_eq!(
+            "0=1,x".parse::<Patch>(),
+            Err(PatchError::InvalidValue("x".to_string()))
+        );
+        assert_eq!(
+            format!("{}=1,2", usize::MAX).parse::<Patch>(),
+            Err(PatchError::AddressOutOfRange(usize::MAX))
+        );
+        assert_eq!(
+            format!("{}=1", usize::MAX).parse::<Patch>().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_last_value_wins() {
+        let mut patch = "0=1,2,3".parse::<Patch>().unwrap();
+        patch.merge(&"1=5".parse().unwrap());
+
+        assert_eq!(patch.runs(), vec![(0, vec![1, 5, 3])]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let patch = "1=12,2 6=1".parse::<Patch>().unwrap();
+        let mut program = parse("1,0,0,0,99");
+        patch.apply(&mut program).unwrap();
+
+        assert_eq!(program, vec![1, 12, 2, 0, 99, 0, 1]);
+
+        // nothing is applied when a cell is too far
+        assert_eq!(
+            "1=5 1000000000000=1"
+                .parse::<Patch>()
+                .unwrap()
+                .apply(&mut program),
+            Err(PatchError::AddressOutOfRange(1000000000000))
+        );
+        assert_eq!(
+            format!("{}=1", usize::MAX)
+                .parse::<Patch>()
+                .unwrap()
+                .apply(&mut program),
+            Err(PatchError::AddressOutOfRange(usize::MAX))
+        );
+        assert_eq!(program, vec![1, 12, 2, 0, 99, 0, 1]);
+
+        let mut cpu = CPU::new(parse("1,0,0,0,99,30,40,50"), 0, None);
+        cpu.patch(&"1=5 2=6".parse().unwrap());
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+        assert_eq!(cpu.memory(0), 70);
+    }
+
+    #[test]
+    fn test_from_changes() {
+        let mut cpu = CPU::new(parse("1,5,6,0,99,30,40"), 0, None);
+        let start = cpu.fork();
+        cpu.run().unwrap();
+
+        let changes = start.diff(&cpu);
+        assert_eq!(
+            changes,
+            vec![Change {
+                address: 0,
+                old: 1,
+                new: 70
+            }]
+        );
+
+        let mut copy = start.fork();
+        copy.patch(&Patch::from_changes(&changes));
+        assert!(copy.diff(&cpu).is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::disasm::{self, Line, Listing};
+use crate::Opcode;
+
+const HOT_ADDRESSES: usize = 20;
+
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    instructions: u64,
+    addresses: BTreeMap<usize, u64>,
+    opcodes: BTreeMap<&'static str, u64>,
+    branches: BTreeMap<usize, (u64, u64)>,
+    runs: Vec<u64>,
+    // the halt last executed, a halted cpu runs it again on every run
+    halted: Option<usize>,
+}
+
+impl Profile {
+    pub(crate) fn record(&mut self, ip: usize, opcode: &Opcode, next_ip: usize) {
+        if let Opcode::Halt = opcode {
+            if self.halted == Some(ip) {
+                return;
+            }
+            self.halted = Some(ip);
+        } else {
+            self.halted = None;
+        }
+
+        self.instructions += 1;
+        *self.addresses.entry(ip).or_default() += 1;
+
+        *self.opcodes.entry(disasm::mnemonic(opcode)).or_default() += 1;
+
+        // a jump to the next instruction is counted as not taken
+        if let Opcode::IfNEq(..) | Opcode::IfEq(..) = opcode {
+            let (taken, not_taken) = self.branches.entry(ip).or_default();
+            if next_ip == ip + 3 {
+                *not_taken += 1;
+            } else {
+                *taken += 1;
+            }
+        }
+    }
+
+    pub(crate) fn record_run(&mut self, instructions: u64) {
+        self.runs.push(instructions);
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn count(&self, address: usize) -> u64 {
+        self.addresses.get(&address).copied().unwrap_or_default()
+    }
+
+    pub fn opcode_count(&self, mnemonic: &str) -> u64 {
+        self.opcodes.get(mnemonic).copied().unwrap_or_default()
+    }
+
+    pub fn branch(&self, address: usize) -> Option<(u64, u64)> {
+        self.branches.get(&address).copied()
+    }
+
+    pub fn runs(&self) -> &[u64] {
+        &self.runs
+    }
+
+    pub fn hot_addresses(&self, limit: usize) -> Vec<(usize, u64)> {
+        let mut addresses = self
+            .addresses
+            .iter()
+            .map(|(&address, &count)| (address, count))
+            .collect::<Vec<_>>();
+        addresses.sort_by(|(a1, c1), (a2, c2)| c2.cmp(c1).then(a1.cmp(a2)));
+        addresses.truncate(limit);
+        addresses
+    }
+
+    // instructions of the listing executed at least once, and all of them
+    pub fn coverage(&self, listing: &Listing) -> (usize, usize) {
+        listing
+            .lines()
+            .iter()
+            .filter(|line| matches!(line, Line::Instruction { .. }))
+            .fold((0, 0), |(executed, total), line| {
+                if self.count(line.address()) > 0 {
+                    (executed + 1, total + 1)
+                } else {
+                    (executed, total + 1)
+                }
+            })
+    }
+
+    fn annotation(&self, line: &Line) -> Option<String> {
+        match line {
+            Line::Instruction { address, .. } => {
+                match (self.count(*address), self.branch(*address)) {
+                    (0, _) => Some("never".to_string()),
+                    (count, Some((taken, not_taken))) => Some(format!(
+                        "x{} taken {} not taken {}",
+                        count, taken, not_taken
+                    )),
+                    (count, None) => Some(format!("x{}", count)),
+                }
+            }
+            Line::Data { address, .. } => {
+                let count = (*address..*address + line.words())
+                    .map(|address| self.count(address))
+                    .sum::<u64>();
+                if count > 0 {
+                    Some(format!("executed x{}", count))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    pub fn annotate(&self, listing: &Listing) -> String {
+        let mut output = String::new();
+        listing
+            .write_annotated(&mut output, |line| self.annotation(line))
+            .expect("cannot write listing");
+        output
+    }
+
+    pub fn to_json(&self) -> String {
+        let object = |entries: Vec<String>| format!("{{{}}}", entries.join(","));
+
+        object(vec![
+            format!("\"instructions\":{}", self.instructions),
+            format!(
+                "\"runs\":[{}]",
+                self.runs
+                    .iter()
+                    .map(|run| run.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            format!(
+                "\"addresses\":{}",
+                object(
+                    self.addresses
+                        .iter()
+                        .map(|(address, count)| format!("\"{}\":{}", address, count))
+                        .collect()
+                )
+            ),
+            format!(
+                "\"opcodes\":{}",
+                object(
+                    self.opcodes
+                        .iter()
+                        .map(|(mnemonic, count)| format!("\"{}\":{}", mnemonic, count))
+                        .collect()
+                )
+            ),
+            format!(
+                "\"branches\":{}",
+                object(
+                    self.branches
+                        .iter()
+                        .map(|(address, (taken, not_taken))| {
+                            format!(
+                                "\"{}\":{{\"taken\":{},\"not_taken\":{}}}",
+                                address, taken, not_taken
+                            )
+                        })
+                        .collect()
+                )
+            ),
+        ])
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        writeln!(fmt, "instructions: {}", self.instructions)?;
+
+        if !self.runs.is_empty() {
+            writeln!(
+                fmt,
+                "runs: {} (min {}, max {}, mean {})",
+                self.runs.len(),
+                self.runs.iter().min().unwrap(),
+                self.runs.iter().max().unwrap(),
+                self.runs.iter().sum::<u64>() / self.runs.len() as u64
+            )?;
+        }
+
+        writeln!(fmt, "opcodes:")?;
+        for (mnemonic, count) in &self.opcodes {
+            writeln!(fmt, "  {:<4}{:>12}", mnemonic, count)?;
+        }
+
+        writeln!(fmt, "hot addresses:")?;
+        for (address, count) in self.hot_addresses(HOT_ADDRESSES) {
+            writeln!(fmt, "  {:>5}{:>12}", address, count)?;
+        }
+
+        writeln!(fmt, "branches:")?;
+        for (address, (taken, not_taken)) in &self.branches {
+            writeln!(
+                fmt,
+                "  {:>5} taken {:>10} not taken {:>10}",
+                address, taken, not_taken
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm::assemble;
+    use crate::disasm::disassemble;
+    use crate::{parse, Memory, Run, CPU};
+
+    const COUNTER: &str = r"
+loop:   add  [counter], #1, [counter]
+        lt   [counter], #5, [flag]
+        jnz  [flag], #loop
+        out  [counter]
+        hlt
+        data 1, 2
+counter: data 0
+flag:   data 0
+";
+
+    #[test]
+    fn test_disabled_by_default() {
+        let mut cpu = CPU::new(assemble(COUNTER).unwrap(), 0, None);
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(5))));
+        assert!(cpu.profile().is_none());
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut cpu = CPU::new(assemble(COUNTER).unwrap(), 0, None);
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(5))));
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+
+        let profile = cpu.take_profile().unwrap();
+
+        assert_eq!(profile.instructions(), 5 * 3 + 2);
+        assert_eq!(profile.runs(), &[16, 1]);
+        assert_eq!(profile.count(0), 5);
+        assert_eq!(profile.count(11), 1);
+        assert_eq!(profile.count(13), 1);
+        assert_eq!(profile.count(14), 0);
+        assert_eq!(profile.opcode_count("add"), 5);
+        assert_eq!(profile.opcode_count("mul"), 0);
+        assert_eq!(profile.branch(8), Some((4, 1)));
+        assert_eq!(profile.hot_addresses(2), vec![(0, 5), (4, 5)]);
+    }
+
+    #[test]
+    fn test_halt_is_counted_once() {
+        let mut cpu = CPU::new(parse("99"), 0, None);
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+
+        let profile = cpu.profile().unwrap();
+
+        assert_eq!(profile.instructions(), 1);
+        assert_eq!(profile.count(0), 1);
+        assert_eq!(profile.runs(), &[1, 0]);
+    }
+
+    #[test]
+    fn test_far_address() {
+        // writes a halt far away and jumps to it
+        let mut cpu = CPU::new(
+            parse("1101,99,0,1000000000000,1105,1,1000000000000"),
+            0,
+            None,
+        );
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+
+        let profile = cpu.profile().unwrap();
+
+        assert_eq!(profile.count(1_000_000_000_000), 1);
+        assert_eq!(profile.hot_addresses(1), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_input_is_counted_once() {
+        let mut cpu = CPU::new(assemble("in [3]\nhlt").unwrap(), 0, None);
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+        cpu.set_input(Some(1));
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+
+        let profile = cpu.profile().unwrap();
+
+        assert_eq!(profile.count(0), 1);
+        assert_eq!(profile.runs(), &[0, 2]);
+    }
+
+    #[test]
+    fn test_json() {
+        let mut cpu = CPU::new(assemble("jz #0, #4\nhlt\nout #1\nhlt").unwrap(), 0, None);
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(1))));
+
+        assert_eq!(
+            cpu.profile().unwrap().to_json(),
+            r#"{"instructions":2,"runs":[2],"addresses":{"0":1,"4":1},"opcodes":{"jz":1,"out":1},"branches":{"0":{"taken":1,"not_taken":0}}}"#
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let program = assemble(COUNTER).unwrap();
+        let mut cpu = CPU::new(program.to_owned(), 0, None);
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::Output(5))));
+
+        let listing = disassemble(&program);
+        let profile = cpu.profile().unwrap();
+
+        assert_eq!(profile.coverage(&listing), (4, 5));
+        assert_eq!(
+            profile.annotate(&listing),
+            r"    0: add [16], #1, [16]                ; <- 8; x5
+    4: lt  [16], #5, [17]                ; x5
+    8: jnz [17], #0                      ; -> 0; x5 taken 4 not taken 1
+   11: out [16]                          ; x1
+   13: hlt                               ; never
+   14: data 1, 2, 0, 0
+"
+        );
+    }
+
+    #[test]
+    fn test_hot_loop() {
+        let program: Vec<Memory> = parse(include_str!("../../day19/data.txt"));
+        let mut cpu = CPU::new(program.to_owned(), 0, Some(10));
+        cpu.enable_profile();
+
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+        cpu.set_input(Some(10));
+        assert!(matches!(cpu.run(), Ok(Run::Output(_))));
+
+        let profile = cpu.profile().unwrap();
+        let (address, count) = profile.hot_addresses(1)[0];
+
+        assert!(count > 1);
+        assert!(profile
+            .annotate(&disassemble(&program))
+            .lines()
+            .any(|line| line.starts_with(&format!("{:>5}:", address))
+                && line.ends_with(&format!("x{}", count))));
+    }
+}
@@ -0,0 +1,234 @@
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+
+use crate::SEPARATOR;
+
+const COMMENTS: &[u8] = b"#;";
+
+#[derive(Debug)]
+pub enum ParseError {
+    InvalidValue(usize, usize, String),
+    EmptyValue(usize, usize),
+    Io(io::Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ParseError::InvalidValue(index, offset, token) => write!(
+                fmt,
+                "invalid value {:?} at index {} (byte {})",
+                token, index, offset
+            ),
+            ParseError::EmptyValue(index, offset) => {
+                write!(fmt, "missing value at index {} (byte {})", index, offset)
+            }
+            ParseError::Io(e) => write!(fmt, "cannot read program: {}", e),
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<T> {
+    values: Vec<T>,
+    token: Vec<u8>,
+    start: usize,
+    offset: usize,
+    line_start: bool,
+    comment: bool,
+}
+
+impl<T: FromStr> Parser<T> {
+    fn new() -> Self {
+        Self {
+            values: vec![],
+            token: vec![],
+            start: 0,
+            offset: 0,
+            line_start: true,
+            comment: false,
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), ParseError> {
+        let offset = self.offset;
+        self.offset += 1;
+
+        if self.comment {
+            if byte == b'\n' {
+                self.comment = false;
+                self.line_start = true;
+            }
+            return Ok(());
+        }
+
+        match byte {
+            // kept inside a value, so that "1 2" is not read as 12
+            _ if byte.is_ascii_whitespace() => {
+                if byte == b'\n' {
+                    self.line_start = true;
+                }
+                if !self.token.is_empty() {
+                    self.token.push(byte);
+                }
+            }
+            _ if self.line_start && self.token.is_empty() && COMMENTS.contains(&byte) => {
+                self.comment = true;
+            }
+            _ if byte == SEPARATOR as u8 => {
+                if self.token.is_empty() {
+                    return Err(ParseError::EmptyValue(self.values.len(), offset));
+                }
+                self.value()?;
+            }
+            _ => {
+                if self.token.is_empty() {
+                    self.start = offset;
+                }
+                self.token.push(byte);
+                self.line_start = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<(), ParseError> {
+        let token = String::from_utf8_lossy(&self.token);
+        let token = token.trim_end();
+        let value = token.parse().map_err(|_| {
+            ParseError::InvalidValue(self.values.len(), self.start, token.to_string())
+        })?;
+        self.values.push(value);
+        self.token.clear();
+
+        Ok(())
+    }
+
+    // a trailing separator is allowed, so the program may end with either
+    fn finish(mut self) -> Result<Vec<T>, ParseError> {
+        if !self.token.is_empty() {
+            self.value()?;
+        }
+
+        Ok(self.values)
+    }
+}
+
+// values are separated by commas, a line may be broken after a comma and
+// lines starting with # or ; are comments
+pub fn parse<T: FromStr>(data: &str) -> Result<Vec<T>, ParseError> {
+    read(data.as_bytes())
+}
+
+pub fn read<T: FromStr, R: BufRead>(mut reader: R) -> Result<Vec<T>, ParseError> {
+    let mut parser = Parser::new();
+
+    loop {
+        let buffer = reader.fill_buf().map_err(ParseError::Io)?;
+        if buffer.is_empty() {
+            break parser.finish();
+        }
+
+        for &byte in buffer {
+            parser.push(byte)?;
+        }
+
+        let length = buffer.len();
+        reader.consume(length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Memory;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse::<Memory>("1,9,10,3,2,3,11,0,99,30,40,50").unwrap(),
+            vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]
+        );
+        assert_eq!(parse::<Memory>("").unwrap(), vec![]);
+        assert_eq!(parse::<Memory>("\n\n").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_whitespace_and_comments() {
+        let program = r"
+# generated program
+; with two comment styles
+  104, -1,
+  99
+
+";
+
+        assert_eq!(parse::<Memory>(program).unwrap(), vec![104, -1, 99]);
+        assert_eq!(parse::<Memory>("1,2,3,\n").unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse::<Memory>(" 1 , 2 ,3\r\n").unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_large_values() {
+        assert_eq!(
+            parse::<Memory>("104,1180591620717411303424,99").unwrap(),
+            vec![104, 1_180_591_620_717_411_303_424, 99]
+        );
+        assert!(matches!(
+            parse::<i64>("104,1180591620717411303424,99"),
+            Err(ParseError::InvalidValue(1, 4, _))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        match parse::<Memory>("1,2,x3,4") {
+            Err(ParseError::InvalidValue(2, 4, token)) => assert_eq!(token, "x3"),
+            result => panic!("invalid result {:?}", result),
+        }
+        assert!(matches!(
+            parse::<Memory>("1,2\n3,4"),
+            Err(ParseError::InvalidValue(1, 2, _))
+        ));
+        assert!(matches!(
+            parse::<Memory>("1,,2"),
+            Err(ParseError::EmptyValue(1, 2))
+        ));
+        assert!(matches!(
+            parse::<Memory>("1,2 # 3"),
+            Err(ParseError::InvalidValue(1, 2, _))
+        ));
+        assert_eq!(
+            parse::<Memory>("\n1,\n,2").unwrap_err().to_string(),
+            "missing value at index 1 (byte 4)"
+        );
+    }
+
+    #[test]
+    fn test_read() {
+        let program = (0..10_000)
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        let values: Vec<Memory> = read(BufReader::with_capacity(7, program.as_bytes())).unwrap();
+
+        assert_eq!(values, (0..10_000).collect::<Vec<_>>());
+        assert!(matches!(
+            read::<Memory, _>(BufReader::with_capacity(3, "1,22,3x3".as_bytes())),
+            Err(ParseError::InvalidValue(2, 5, _))
+        ));
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::error;
+use std::fmt;
+
+use crate::budget::Budget;
+use crate::device::Control;
+use crate::{Error, Memory, Step, CPU};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    Round,
+    Quiescent,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Paused,
+    Halted,
+    Deadlock,
+}
+
+#[derive(Debug)]
+pub enum SchedulerError {
+    CPU(usize, Error),
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            SchedulerError::CPU(id, e) => write!(fmt, "cpu {} failed: {}", id, e),
+        }
+    }
+}
+
+impl error::Error for SchedulerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SchedulerError::CPU(_, e) => Some(e),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Queues {
+    queues: BTreeMap<String, VecDeque<Memory>>,
+}
+
+impl Queues {
+    pub fn push(&mut self, name: &str, value: Memory) {
+        self.queue(name).push_back(value);
+    }
+
+    pub fn extend<I: IntoIterator<Item = Memory>>(&mut self, name: &str, values: I) {
+        self.queue(name).extend(values);
+    }
+
+    pub fn pop(&mut self, name: &str) -> Option<Memory> {
+        self.queues.get_mut(name)?.pop_front()
+    }
+
+    pub fn len(&self, name: &str) -> usize {
+        self.queues.get(name).map_or(0, VecDeque::len)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VecDeque<Memory>> {
+        self.queues.get(name)
+    }
+
+    fn queue(&mut self, name: &str) -> &mut VecDeque<Memory> {
+        if !self.queues.contains_key(name) {
+            self.queues.insert(name.to_string(), VecDeque::new());
+        }
+        self.queues.get_mut(name).unwrap()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Ready,
+    Blocked,
+    Halted,
+}
+
+struct Process {
+    cpu: CPU,
+    input: String,
+    output: String,
+    idle: Option<Memory>,
+    state: State,
+}
+
+// runs many cpus round-robin on the current thread, each one reads from and
+// writes to a named queue
+pub struct Scheduler {
+    processes: Vec<Process>,
+    queues: Queues,
+    slice: u64,
+}
+
+impl Scheduler {
+    // slice is the number of steps a cpu runs before the next one takes over
+    pub fn new(slice: u64) -> Self {
+        Self {
+            processes: vec![],
+            queues: Queues::default(),
+            slice: slice.max(1),
+        }
+    }
+
+    // with an idle value the cpu reads it when its queue is empty instead of
+    // waiting, and counts as blocked until it outputs or reads a real value
+    pub fn add(&mut self, cpu: CPU, input: &str, output: &str, idle: Option<Memory>) -> usize {
+        self.processes.push(Process {
+            cpu,
+            input: input.to_string(),
+            output: output.to_string(),
+            idle,
+            state: State::Ready,
+        });
+
+        self.processes.len() - 1
+    }
+
+    pub fn cpu(&self, id: usize) -> &CPU {
+        &self.processes[id].cpu
+    }
+
+    pub fn cpu_mut(&mut self, id: usize) -> &mut CPU {
+        &mut self.processes[id].cpu
+    }
+
+    pub fn is_halted(&self, id: usize) -> bool {
+        self.processes[id].state == State::Halted
+    }
+
+    pub fn queues(&self) -> &Queues {
+        &self.queues
+    }
+
+    pub fn queues_mut(&mut self) -> &mut Queues {
+        &mut self.queues
+    }
+
+    pub fn into_cpus(self) -> Vec<CPU> {
+        self.processes
+            .into_iter()
+            .map(|process| process.cpu)
+            .collect()
+    }
+
+    // the hook is called after every round and when every cpu is blocked on
+    // an empty queue, pausing from the hook returns and a later run resumes
+    pub fn run<F>(&mut self, mut hook: F) -> Result<Stop, SchedulerError>
+    where
+        F: FnMut(Event, &mut Queues) -> Control,
+    {
+        loop {
+            for id in 0..self.processes.len() {
+                self.run_slice(id).map_err(|e| SchedulerError::CPU(id, e))?;
+            }
+
+            if hook(Event::Round, &mut self.queues) == Control::Pause {
+                break Ok(Stop::Paused);
+            }
+
+            if self
+                .processes
+                .iter()
+                .all(|process| process.state == State::Halted)
+            {
+                break Ok(Stop::Halted);
+            }
+
+            if self.is_quiescent() {
+                if hook(Event::Quiescent, &mut self.queues) == Control::Pause {
+                    break Ok(Stop::Paused);
+                }
+
+                if self.is_quiescent()
+                    && self.processes.iter().all(|process| process.idle.is_none())
+                {
+                    break Ok(Stop::Deadlock);
+                }
+            }
+        }
+    }
+
+    fn is_quiescent(&self) -> bool {
+        self.processes.iter().all(|process| match process.state {
+            State::Ready => false,
+            State::Blocked => self.queues.len(&process.input) == 0,
+            State::Halted => true,
+        })
+    }
+
+    fn run_slice(&mut self, id: usize) -> Result<(), Error> {
+        let process = &mut self.processes[id];
+        if process.state == State::Halted
+            || process.state == State::Blocked
+                && process.idle.is_none()
+                && self.queues.len(&process.input) == 0
+        {
+            return Ok(());
+        }
+
+        let mut budget = self.slice;
+        loop {
+            match process.cpu.step_budget(&mut budget)? {
+                Budget::Done(Step::Continue) => {}
+                Budget::Done(Step::NeedInput) => match self.queues.pop(&process.input) {
+                    Some(value) => {
+                        process.cpu.push_input(value);
+                        process.state = State::Ready;
+                    }
+                    // a polling cpu yields the rest of its slice
+                    None => {
+                        process.state = State::Blocked;
+                        if let Some(value) = process.idle {
+                            process.cpu.push_input(value);
+                        }
+                        break;
+                    }
+                },
+                Budget::Done(Step::Output(value)) => {
+                    self.queues.push(&process.output, value);
+                    process.state = State::Ready;
+                }
+                Budget::Done(Step::Halt) => {
+                    process.state = State::Halted;
+                    break;
+                }
+                Budget::Exhausted => break,
+                // spinning without any i/o is as good as waiting
+                Budget::Loop => {
+                    process.state = State::Blocked;
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::parse;
+
+    // outputs a + 10 * b
+    const SUM: &str = r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#;
+
+    #[test]
+    fn test_pipeline() {
+        let mut scheduler = Scheduler::new(3);
+        scheduler.add(CPU::new(parse(SUM), 0, None), "a", "b", None);
+        scheduler.add(CPU::new(parse(SUM), 0, None), "b", "c", None);
+        scheduler.queues_mut().extend("a", vec![1, 2]);
+        scheduler.queues_mut().push("b", 3);
+
+        assert!(matches!(
+            scheduler.run(|_, _| Control::Continue),
+            Ok(Stop::Halted)
+        ));
+        assert_eq!(scheduler.queues_mut().pop("c"), Some(3 + 210));
+        assert!(scheduler.is_halted(0) && scheduler.is_halted(1));
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let mut scheduler = Scheduler::new(100);
+        scheduler.add(CPU::new(parse(SUM), 0, None), "a", "b", None);
+        scheduler.add(CPU::new(parse(SUM), 0, None), "b", "a", None);
+
+        let mut events = vec![];
+        let stop = scheduler.run(|event, _| {
+            events.push(event);
+            Control::Continue
+        });
+
+        assert!(matches!(stop, Ok(Stop::Deadlock)));
+        assert_eq!(events, vec![Event::Round, Event::Quiescent]);
+    }
+
+    #[test]
+    fn test_quiescent_hook() {
+        // adds up its input until it reads zero, then outputs the sum
+        let program = assemble(
+            r"
+next:   in   [n]
+        jz   [n], #done
+        add  [sum], [n], [sum]
+        jmp  #next
+done:   out  [sum]
+        hlt
+n:      data 0
+sum:    data 0
+",
+        )
+        .unwrap();
+
+        let mut scheduler = Scheduler::new(2);
+        scheduler.add(CPU::new(program, 0, None), "in", "out", None);
+
+        let mut values = (1..=10).collect::<Vec<_>>();
+        let stop = scheduler.run(|event, queues| {
+            if event == Event::Quiescent {
+                queues.push("in", values.pop().unwrap_or(0));
+            }
+            Control::Continue
+        });
+
+        assert!(matches!(stop, Ok(Stop::Halted)));
+        assert_eq!(scheduler.queues().get("out"), Some(&VecDeque::from([55])));
+    }
+
+    #[test]
+    fn test_idle_input() {
+        // echoes its input, a negative value is the idle value
+        let program = assemble(
+            r"
+next:   in   [n]
+        lt   [n], #0, [flag]
+        jnz  [flag], #next
+        out  [n]
+        jmp  #next
+n:      data 0
+flag:   data 0
+",
+        )
+        .unwrap();
+
+        let mut scheduler = Scheduler::new(10);
+        scheduler.add(CPU::new(program, 0, None), "in", "out", Some(-1));
+        scheduler.queues_mut().extend("in", vec![1, 2, 3]);
+
+        let mut quiescent = 0;
+        let stop = scheduler.run(|event, _| {
+            if event == Event::Quiescent {
+                quiescent += 1;
+            }
+            if quiescent == 3 {
+                Control::Pause
+            } else {
+                Control::Continue
+            }
+        });
+
+        assert!(matches!(stop, Ok(Stop::Paused)));
+        assert_eq!(
+            scheduler.queues().get("out"),
+            Some(&VecDeque::from([1, 2, 3]))
+        );
+
+        scheduler.queues_mut().push("in", 4);
+        assert!(matches!(
+            scheduler.run(|event, _| match event {
+                Event::Round => Control::Continue,
+                Event::Quiescent => Control::Pause,
+            }),
+            Ok(Stop::Paused)
+        ));
+        assert_eq!(scheduler.queues().len("out"), 4);
+    }
+
+    #[test]
+    fn test_error() {
+        let mut scheduler = Scheduler::new(10);
+        scheduler.add(CPU::new(parse(SUM), 0, None), "a", "b", None);
+        scheduler.add(CPU::new(parse(r#"104,1,42"#), 0, None), "b", "c", None);
+
+        let error = scheduler.run(|_, _| Control::Continue).unwrap_err();
+        assert!(matches!(error, SchedulerError::CPU(1, _)));
+        assert!(error.to_string().starts_with("cpu 1 failed: "));
+    }
+
+    #[test]
+    fn test_feedback_loops() {
+        // day 7 part 2: the answer is the last value sent back to the first
+        // amplifier once they all halted
+        let program: Vec<Memory> = parse(include_str!("../../day07/data.txt"));
+        let names = ["a", "b", "c", "d", "e"];
+
+        let mut best = 0;
+        for code in 0..5 * 5 * 5 * 5 * 5 {
+            let phases = (0..5)
+                .scan(code, |code, _| {
+                    let phase = *code % 5 + 5;
+                    *code /= 5;
+                    Some(phase)
+                })
+                .collect::<Vec<Memory>>();
+            if (5..10).any(|phase| !phases.contains(&phase)) {
+                continue;
+            }
+
+            let mut scheduler = Scheduler::new(50);
+            for (index, phase) in phases.iter().enumerate() {
+                let (input, output) = (names[index], names[(index + 1) % 5]);
+                scheduler.add(CPU::new(program.to_owned(), 0, None), input, output, None);
+                scheduler.queues_mut().push(input, *phase);
+            }
+            scheduler.queues_mut().push("a", 0);
+
+            assert!(matches!(
+                scheduler.run(|_, _| Control::Continue),
+                Ok(Stop::Halted)
+            ));
+            best = best.max(scheduler.queues_mut().pop("a").unwrap());
+        }
+
+        assert_eq!(best, 1_336_480);
+    }
+}
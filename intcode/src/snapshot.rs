@@ -0,0 +1,754 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryInto;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::isa::Isa;
+use crate::memory::MemoryData;
+use crate::word::Overflow;
+use crate::{Memory, CPU};
+
+// version 1 stored at most one pending input value, version 2 the whole
+// queue, version 3 the overflow policy and the isa
+pub const VERSION: u32 = 3;
+
+// stored as the index of the variant
+const OVERFLOWS: [Overflow; 3] = [Overflow::Trap, Overflow::Wrap, Overflow::Saturate];
+const ISAS: [Isa; 3] = [Isa::Day2, Isa::Day5, Isa::Full];
+
+const MAGIC: &[u8; 4] = b"ICPU";
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    TrailingData(usize),
+    InvalidJson(usize),
+    MissingField(&'static str),
+    InvalidField(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            SnapshotError::Io(e) => write!(fmt, "{}", e),
+            SnapshotError::InvalidMagic => write!(fmt, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(fmt, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::Truncated => write!(fmt, "truncated snapshot"),
+            SnapshotError::TrailingData(position) => {
+                write!(fmt, "trailing data at byte {}", position)
+            }
+            SnapshotError::InvalidJson(position) => {
+                write!(fmt, "invalid json at byte {}", position)
+            }
+            SnapshotError::MissingField(name) => write!(fmt, "missing field {}", name),
+            SnapshotError::InvalidField(name) => write!(fmt, "invalid field {}", name),
+        }
+    }
+}
+
+impl error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// a tiny json model, enough to read back what to_json writes
+#[derive(Debug, PartialEq)]
+enum Json {
+    Null,
+    Number(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], SnapshotError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or(SnapshotError::Truncated)?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self, field: &'static str) -> Result<usize, SnapshotError> {
+        self.u64()?
+            .try_into()
+            .map_err(|_| SnapshotError::InvalidField(field))
+    }
+
+    fn memory(&mut self) -> Result<Memory, SnapshotError> {
+        Ok(Memory::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+}
+
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.position)
+            .map(u8::is_ascii_whitespace)
+            .unwrap_or(false)
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SnapshotError> {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(SnapshotError::InvalidJson(self.position))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        self.expect(b'"')?;
+        let start = self.position;
+        while let Some(&byte) = self.bytes.get(self.position) {
+            self.position += 1;
+            match byte {
+                b'"' => {
+                    return String::from_utf8(self.bytes[start..self.position - 1].to_vec())
+                        .map_err(|_| SnapshotError::InvalidJson(start))
+                }
+                b'\\' => return Err(SnapshotError::InvalidJson(self.position - 1)),
+                _ => {}
+            }
+        }
+        Err(SnapshotError::InvalidJson(self.position))
+    }
+
+    fn sequence<T, F>(&mut self, close: u8, mut item: F) -> Result<Vec<T>, SnapshotError>
+    where
+        F: FnMut(&mut Self) -> Result<T, SnapshotError>,
+    {
+        let mut items = vec![];
+        if self.peek() == Some(close) {
+            self.position += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(byte) if byte == close => {
+                    self.position += 1;
+                    break Ok(items);
+                }
+                _ => break Err(SnapshotError::InvalidJson(self.position)),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, SnapshotError> {
+        match self.peek() {
+            Some(b'{') => {
+                self.position += 1;
+                let entries = self.sequence(b'}', |parser| {
+                    let key = parser.string()?;
+                    parser.expect(b':')?;
+                    Ok((key, parser.value()?))
+                })?;
+                Ok(Json::Object(entries.into_iter().collect()))
+            }
+            Some(b'[') => {
+                self.position += 1;
+                Ok(Json::Array(self.sequence(b']', Self::value)?))
+            }
+            Some(b'n') if self.bytes[self.position..].starts_with(b"null") => {
+                self.position += 4;
+                Ok(Json::Null)
+            }
+            Some(byte) if byte == b'-' || byte.is_ascii_digit() => {
+                let start = self.position;
+                self.position += 1;
+                while self
+                    .bytes
+                    .get(self.position)
+                    .map(u8::is_ascii_digit)
+                    .unwrap_or(false)
+                {
+                    self.position += 1;
+                }
+                Ok(Json::Number(
+                    String::from_utf8(self.bytes[start..self.position].to_vec()).unwrap(),
+                ))
+            }
+            _ => Err(SnapshotError::InvalidJson(self.position)),
+        }
+    }
+}
+
+impl Json {
+    fn parse(json: &str) -> Result<Json, SnapshotError> {
+        let mut parser = JsonParser {
+            bytes: json.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value()?;
+        match parser.peek() {
+            None => Ok(value),
+            Some(_) => Err(SnapshotError::InvalidJson(parser.position)),
+        }
+    }
+
+    fn field(&self, name: &'static str) -> Result<&Json, SnapshotError> {
+        match self {
+            Json::Object(fields) => fields.get(name).ok_or(SnapshotError::MissingField(name)),
+            _ => Err(SnapshotError::InvalidField(name)),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &'static str) -> Result<T, SnapshotError> {
+        match self {
+            Json::Number(value) => value.parse().map_err(|_| SnapshotError::InvalidField(name)),
+            _ => Err(SnapshotError::InvalidField(name)),
+        }
+    }
+
+    fn array(&self, name: &'static str) -> Result<&[Json], SnapshotError> {
+        match self {
+            Json::Array(values) => Ok(values),
+            _ => Err(SnapshotError::InvalidField(name)),
+        }
+    }
+}
+
+fn code<T: PartialEq>(variants: &[T], value: T) -> u8 {
+    variants
+        .iter()
+        .position(|variant| *variant == value)
+        .unwrap() as u8
+}
+
+fn variant<T: Copy>(variants: &[T], code: usize, name: &'static str) -> Result<T, SnapshotError> {
+    variants
+        .get(code)
+        .copied()
+        .ok_or(SnapshotError::InvalidField(name))
+}
+
+fn join<I>(values: I) -> String
+where
+    I: IntoIterator,
+    I::Item: ToString,
+{
+    values
+        .into_iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl CPU {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let segments = self.memory.segments();
+
+        let mut bytes = Vec::with_capacity(
+            64 + segments
+                .iter()
+                .map(|(_, values)| 16 + values.len() * 16)
+                .sum::<usize>(),
+        );
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.ip as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.base.to_le_bytes());
+        bytes.extend_from_slice(&(self.input.len() as u64).to_le_bytes());
+        for input in &self.input {
+            bytes.extend_from_slice(&input.to_le_bytes());
+        }
+        bytes.push(code(&OVERFLOWS, self.overflow));
+        bytes.push(code(&ISAS, self.isa));
+        bytes.extend_from_slice(&(self.memory.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(segments.len() as u64).to_le_bytes());
+        for (start, values) in segments {
+            bytes.extend_from_slice(&(start as u64).to_le_bytes());
+            bytes.extend_from_slice(&(values.len() as u64).to_le_bytes());
+            for value in values {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let ip = reader.usize("ip")?;
+        let base = reader.memory()?;
+        let input = if version == 1 {
+            match reader.u8()? {
+                0 => VecDeque::new(),
+                1 => VecDeque::from(vec![reader.memory()?]),
+                _ => return Err(SnapshotError::InvalidField("input")),
+            }
+        } else {
+            let count = reader.usize("input")?;
+            if count > bytes.len() {
+                return Err(SnapshotError::Truncated);
+            }
+            (0..count)
+                .map(|_| reader.memory())
+                .collect::<Result<_, _>>()?
+        };
+        let (overflow, isa) = if version >= 3 {
+            (
+                variant(&OVERFLOWS, reader.u8()?.into(), "overflow")?,
+                variant(&ISAS, reader.u8()?.into(), "isa")?,
+            )
+        } else {
+            (Overflow::default(), Isa::default())
+        };
+        let len = reader.usize("len")?;
+
+        let mut segments = vec![];
+        for _ in 0..reader.u64()? {
+            let start = reader.usize("memory")?;
+            let count = reader.usize("memory")?;
+            if count > bytes.len() {
+                return Err(SnapshotError::Truncated);
+            }
+            let values = (0..count)
+                .map(|_| reader.memory())
+                .collect::<Result<Vec<_>, _>>()?;
+            segments.push((start, values));
+        }
+
+        if reader.position != bytes.len() {
+            return Err(SnapshotError::TrailingData(reader.position));
+        }
+
+        Ok(Self {
+            memory: MemoryData::from_segments(len, segments)
+                .ok_or(SnapshotError::InvalidField("memory"))?,
+            ip,
+            base,
+            input,
+            overflow,
+            isa,
+            profile: None,
+            loops: None,
+            journal: None,
+            cache: None,
+            transcript: None,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"version":{},"ip":{},"base":{},"input":[{}],"overflow":{},"isa":{},"len":{},"memory":[{}]}}"#,
+            VERSION,
+            self.ip,
+            self.base,
+            join(&self.input),
+            code(&OVERFLOWS, self.overflow),
+            code(&ISAS, self.isa),
+            self.memory.len(),
+            self.memory
+                .segments()
+                .into_iter()
+                .map(|(start, values)| format!(
+                    r#"{{"start":{},"values":[{}]}}"#,
+                    start,
+                    join(&values)
+                ))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        let json = Json::parse(json)?;
+
+        let version = json.field("version")?.number("version")?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let segments = json
+            .field("memory")?
+            .array("memory")?
+            .iter()
+            .map(|segment| {
+                Ok((
+                    segment.field("start")?.number("start")?,
+                    segment
+                        .field("values")?
+                        .array("values")?
+                        .iter()
+                        .map(|value| value.number("values"))
+                        .collect::<Result<Vec<_>, _>>()?,
+                ))
+            })
+            .collect::<Result<Vec<_>, SnapshotError>>()?;
+
+        Ok(Self {
+            memory: MemoryData::from_segments(json.field("len")?.number("len")?, segments)
+                .ok_or(SnapshotError::InvalidField("memory"))?,
+            ip: json.field("ip")?.number("ip")?,
+            base: json.field("base")?.number("base")?,
+            input: match (version, json.field("input")?) {
+                (1, Json::Null) => VecDeque::new(),
+                (1, input) => VecDeque::from(vec![input.number("input")?]),
+                (_, input) => input
+                    .array("input")?
+                    .iter()
+                    .map(|value| value.number("input"))
+                    .collect::<Result<_, _>>()?,
+            },
+            overflow: if version >= 3 {
+                variant(
+                    &OVERFLOWS,
+                    json.field("overflow")?.number("overflow")?,
+                    "overflow",
+                )?
+            } else {
+                Overflow::default()
+            },
+            isa: if version >= 3 {
+                variant(&ISAS, json.field("isa")?.number("isa")?, "isa")?
+            } else {
+                Isa::default()
+            },
+            profile: None,
+            loops: None,
+            journal: None,
+            cache: None,
+            transcript: None,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        fs::write(path, self.to_bytes()).map_err(SnapshotError::Io)
+    }
+
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        fs::write(path, self.to_json()).map_err(SnapshotError::Io)
+    }
+
+    // reads a snapshot written by save or save_json
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        let bytes = fs::read(path).map_err(SnapshotError::Io)?;
+        if bytes.starts_with(MAGIC) {
+            Self::from_bytes(&bytes)
+        } else {
+            Self::from_json(std::str::from_utf8(&bytes).map_err(|_| SnapshotError::InvalidMagic)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Run};
+
+    fn paused_cpu() -> CPU {
+        // reads a value, stores it at 20, outputs it and loops
+        let mut cpu = CPU::new(parse(r#"3,20,4,20,109,-3,1105,1,0"#), 0, Some(7));
+        assert!(matches!(cpu.run(), Ok(Run::Output(7))));
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+        cpu.memory[100_000] = -1;
+        cpu.extend_input([8, 9]);
+        cpu
+    }
+
+    fn assert_same(cpu: &CPU, other: &CPU) {
+        assert_eq!(cpu.ip, other.ip);
+        assert_eq!(cpu.base, other.base);
+        assert_eq!(cpu.input, other.input);
+        assert_eq!(cpu.overflow, other.overflow);
+        assert_eq!(cpu.isa, other.isa);
+        assert_eq!(cpu.memory.len(), other.memory.len());
+        assert_eq!(cpu.memory.segments(), other.memory.segments());
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let cpu = paused_cpu();
+        let mut restored = CPU::from_bytes(&cpu.to_bytes()).expect("invalid snapshot");
+
+        assert_same(&cpu, &restored);
+        assert_eq!(restored.base, -3);
+        assert!(matches!(restored.run(), Ok(Run::Output(8))));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let cpu = paused_cpu();
+        let mut restored = CPU::from_json(&cpu.to_json()).expect("invalid snapshot");
+
+        assert_same(&cpu, &restored);
+        assert!(matches!(restored.run(), Ok(Run::Output(8))));
+    }
+
+    #[test]
+    fn test_json_format() {
+        let cpu = CPU::new(parse(r#"104,1,99"#), 0, None);
+
+        assert_eq!(
+            cpu.to_json(),
+            r#"{"version":3,"ip":0,"base":0,"input":[],"overflow":0,"isa":2,"len":3,"memory":[{"start":0,"values":[104,1,99]}]}"#
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        for overflow in OVERFLOWS {
+            let mut cpu = paused_cpu();
+            cpu.set_overflow(overflow);
+
+            assert_same(&cpu, &CPU::from_bytes(&cpu.to_bytes()).unwrap());
+            assert_same(&cpu, &CPU::from_json(&cpu.to_json()).unwrap());
+        }
+
+        assert!(matches!(
+            CPU::from_json(
+                r#"{"version":3,"ip":0,"base":0,"input":[],"overflow":3,"isa":2,"len":0,"memory":[]}"#
+            ),
+            Err(SnapshotError::InvalidField("overflow"))
+        ));
+    }
+
+    #[test]
+    fn test_isa() {
+        for isa in ISAS {
+            let mut cpu = CPU::new(parse(r#"1101,1,1,0,99"#), 0, None);
+            cpu.set_isa(isa);
+
+            assert_same(&cpu, &CPU::from_bytes(&cpu.to_bytes()).unwrap());
+            assert_same(&cpu, &CPU::from_json(&cpu.to_json()).unwrap());
+        }
+
+        // a restored day 2 cpu still rejects parameter modes
+        let mut cpu = CPU::new(parse(r#"1101,1,1,0,99"#), 0, None);
+        cpu.set_isa(Isa::Day2);
+        let mut restored = CPU::from_bytes(&cpu.to_bytes()).unwrap();
+        assert!(restored.run().is_err());
+
+        assert!(matches!(
+            CPU::from_json(
+                r#"{"version":3,"ip":0,"base":0,"input":[],"overflow":0,"isa":3,"len":0,"memory":[]}"#
+            ),
+            Err(SnapshotError::InvalidField("isa"))
+        ));
+    }
+
+    #[test]
+    fn test_version_2() {
+        let mut bytes = b"ICPU".to_vec();
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&(0 as Memory).to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&(5 as Memory).to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        for value in [104 as Memory, 1, 99] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let cpu = CPU::from_bytes(&bytes).expect("invalid snapshot");
+        assert_eq!(cpu.input, [5]);
+        assert_eq!(cpu.overflow, Overflow::Trap);
+        assert_eq!(cpu.isa, Isa::Full);
+        assert_eq!(cpu.memory.segments(), vec![(0, vec![104, 1, 99])]);
+
+        let json = CPU::from_json(
+            r#"{"version":2,"ip":0,"base":0,"input":[5],"len":3,"memory":[{"start":0,"values":[104,1,99]}]}"#,
+        )
+        .expect("invalid snapshot");
+        assert_same(&cpu, &json);
+    }
+
+    #[test]
+    fn test_version_1() {
+        let mut bytes = b"ICPU".to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        bytes.extend_from_slice(&(-3 as Memory).to_le_bytes());
+        bytes.push(1);
+        bytes.extend_from_slice(&(5 as Memory).to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        for value in [104 as Memory, 1, 99] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let cpu = CPU::from_bytes(&bytes).expect("invalid snapshot");
+        assert_eq!(cpu.ip, 2);
+        assert_eq!(cpu.base, -3);
+        assert_eq!(cpu.input, [5]);
+        assert_eq!(cpu.memory.segments(), vec![(0, vec![104, 1, 99])]);
+
+        let json = CPU::from_json(
+            r#"{"version":1,"ip":2,"base":-3,"input":null,"len":3,"memory":[{"start":0,"values":[104,1,99]}]}"#,
+        )
+        .expect("invalid snapshot");
+        assert_eq!(json.input(), None);
+
+        bytes[8 + 8 + 16] = 2;
+        assert!(matches!(
+            CPU::from_bytes(&bytes),
+            Err(SnapshotError::InvalidField("input"))
+        ));
+    }
+
+    #[test]
+    fn test_large_values() {
+        let cpu = CPU::new(vec![Memory::MIN, Memory::MAX], 0, Some(Memory::MAX));
+
+        assert_same(&cpu, &CPU::from_bytes(&cpu.to_bytes()).unwrap());
+        assert_same(&cpu, &CPU::from_json(&cpu.to_json()).unwrap());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let cpu = paused_cpu();
+        let dir = std::env::temp_dir();
+        let binary = dir.join(format!("intcode-snapshot-{}.bin", std::process::id()));
+        let json = dir.join(format!("intcode-snapshot-{}.json", std::process::id()));
+
+        cpu.save(&binary).unwrap();
+        cpu.save_json(&json).unwrap();
+
+        assert_same(&cpu, &CPU::load(&binary).unwrap());
+        assert_same(&cpu, &CPU::load(&json).unwrap());
+
+        fs::remove_file(binary).unwrap();
+        fs::remove_file(json).unwrap();
+
+        assert!(matches!(
+            CPU::load(dir.join("intcode-snapshot-missing")),
+            Err(SnapshotError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_bytes() {
+        let bytes = paused_cpu().to_bytes();
+
+        assert!(matches!(
+            CPU::from_bytes(b"NOPE"),
+            Err(SnapshotError::InvalidMagic)
+        ));
+        assert!(matches!(
+            CPU::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Truncated)
+        ));
+        assert!(matches!(
+            CPU::from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(SnapshotError::TrailingData(_))
+        ));
+
+        let mut future = bytes.clone();
+        future[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            CPU::from_bytes(&future),
+            Err(SnapshotError::UnsupportedVersion(v)) if v == VERSION + 1
+        ));
+
+        let mut input = bytes;
+        input[8 + 8 + 16..8 + 8 + 16 + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            CPU::from_bytes(&input),
+            Err(SnapshotError::Truncated)
+        ));
+
+        // the start of the only segment, followed by its count and 3 values
+        let mut start = CPU::new(parse(r#"104,1,99"#), 0, None).to_bytes();
+        let offset = start.len() - 3 * 16 - 8 - 8;
+        start[offset..offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            CPU::from_bytes(&start),
+            Err(SnapshotError::InvalidField("memory"))
+        ));
+        assert_eq!(
+            CPU::from_bytes(&start).err().unwrap().to_string(),
+            "invalid field memory"
+        );
+    }
+
+    #[test]
+    fn test_corrupt_json() {
+        assert!(matches!(
+            CPU::from_json(r#"{"version":1,"ip":0"#),
+            Err(SnapshotError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            CPU::from_json(r#"{"version":4}"#),
+            Err(SnapshotError::UnsupportedVersion(4))
+        ));
+        assert!(matches!(
+            CPU::from_json(r#"{"version":1,"ip":0}"#),
+            Err(SnapshotError::MissingField("memory"))
+        ));
+        assert!(matches!(
+            CPU::from_json(r#"{"version":2,"ip":-1,"base":0,"input":[],"len":0,"memory":[]}"#),
+            Err(SnapshotError::InvalidField("ip"))
+        ));
+        assert!(matches!(
+            CPU::from_json(r#"{"version":2,"ip":0,"base":0,"input":1,"len":0,"memory":[]}"#),
+            Err(SnapshotError::InvalidField("input"))
+        ));
+        assert!(matches!(
+            CPU::from_json(
+                r#"{"version":2,"ip":0,"base":0,"input":[],"len":0,"memory":[{"start":18446744073709551615,"values":[1,2]}]}"#
+            ),
+            Err(SnapshotError::InvalidField("memory"))
+        ));
+    }
+}
@@ -0,0 +1,485 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::word::Word;
+use crate::{Error, Memory, Step, CPU};
+
+const HEADER: &str = "# intcode transcript";
+
+#[derive(Debug)]
+pub enum TranscriptError {
+    Io(io::Error),
+    InvalidLine(usize, String),
+    InvalidSteps(usize, String),
+    InvalidValue(usize, String),
+    StepsOutOfOrder(usize),
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            TranscriptError::Io(e) => write!(fmt, "{}", e),
+            TranscriptError::InvalidLine(line, text) => {
+                write!(fmt, "line {}: invalid entry {:?}", line, text)
+            }
+            TranscriptError::InvalidSteps(line, token) => {
+                write!(fmt, "line {}: invalid instruction count {:?}", line, token)
+            }
+            TranscriptError::InvalidValue(line, token) => {
+                write!(fmt, "line {}: invalid value {:?}", line, token)
+            }
+            TranscriptError::StepsOutOfOrder(line) => {
+                write!(fmt, "line {}: instruction count goes backwards", line)
+            }
+        }
+    }
+}
+
+impl error::Error for TranscriptError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<W = Memory> {
+    Input(W),
+    Output(W),
+    Halt,
+}
+
+// an event and the instructions executed up to and including the one that
+// caused it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<W = Memory> {
+    pub steps: u64,
+    pub event: Event<W>,
+}
+
+impl<W: Word> fmt::Display for Entry<W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match &self.event {
+            Event::Input(value) => write!(fmt, "{} in {}", self.steps, value),
+            Event::Output(value) => write!(fmt, "{} out {}", self.steps, value),
+            Event::Halt => write!(fmt, "{} halt", self.steps),
+        }
+    }
+}
+
+// the i/o of a run; the text form has one entry per line, blank lines and
+// lines starting with # are ignored:
+//
+//   12 in 1
+//   3051 out 2457252183
+//   3052 halt
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcript<W = Memory> {
+    entries: Vec<Entry<W>>,
+    steps: u64,
+}
+
+impl<W: Word> Transcript<W> {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            steps: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry<W>] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // the instructions executed while recording, or up to the last entry
+    // for a transcript read back from text
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &W> {
+        self.entries.iter().filter_map(|entry| match &entry.event {
+            Event::Input(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &W> {
+        self.entries.iter().filter_map(|entry| match &entry.event {
+            Event::Output(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    // the input is consumed by the instruction step is about to count
+    #[inline(always)]
+    pub(crate) fn input(&mut self, value: W) {
+        self.entries.push(Entry {
+            steps: self.steps + 1,
+            event: Event::Input(value),
+        });
+    }
+
+    #[inline(always)]
+    pub(crate) fn step(&mut self, step: &Step<W>) {
+        let event = match step {
+            Step::NeedInput => return,
+            // a halted cpu runs its halt again on every run
+            Step::Halt if self.halted() => return,
+            Step::Continue => None,
+            Step::Output(value) => Some(Event::Output(value.clone())),
+            Step::Halt => Some(Event::Halt),
+        };
+
+        self.steps += 1;
+        if let Some(event) = event {
+            self.entries.push(Entry {
+                steps: self.steps,
+                event,
+            });
+        }
+    }
+
+    // takes back the last instruction and its entry, a halt after it is not
+    // in the journal and goes with it
+    pub(crate) fn step_back(&mut self) {
+        if self.halted() {
+            self.entries.pop();
+            self.steps -= 1;
+        }
+        if self.steps > 0 {
+            if self
+                .entries
+                .last()
+                .is_some_and(|entry| entry.steps == self.steps)
+            {
+                self.entries.pop();
+            }
+            self.steps -= 1;
+        }
+    }
+
+    fn halted(&self) -> bool {
+        matches!(
+            self.entries.last(),
+            Some(Entry { steps, event: Event::Halt }) if *steps == self.steps
+        )
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), TranscriptError> {
+        fs::write(path, self.to_string()).map_err(TranscriptError::Io)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TranscriptError> {
+        fs::read_to_string(path)
+            .map_err(TranscriptError::Io)?
+            .parse()
+    }
+}
+
+impl<W: Word> Default for Transcript<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Word> fmt::Display for Transcript<W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        writeln!(fmt, "{}", HEADER)?;
+        for entry in &self.entries {
+            writeln!(fmt, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Word> FromStr for Transcript<W> {
+    type Err = TranscriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut transcript = Self::new();
+        for (index, text) in s.lines().enumerate() {
+            let line = index + 1;
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let tokens = text.split_whitespace().collect::<Vec<_>>();
+            let steps = tokens[0]
+                .parse()
+                .map_err(|_| TranscriptError::InvalidSteps(line, tokens[0].to_string()))?;
+            let value = |token: &str| {
+                token
+                    .parse()
+                    .map_err(|_| TranscriptError::InvalidValue(line, token.to_string()))
+            };
+            let event = match tokens[1..] {
+                ["in", token] => Event::Input(value(token)?),
+                ["out", token] => Event::Output(value(token)?),
+                ["halt"] => Event::Halt,
+                _ => return Err(TranscriptError::InvalidLine(line, text.to_string())),
+            };
+
+            if steps < transcript.steps {
+                return Err(TranscriptError::StepsOutOfOrder(line));
+            }
+            transcript.steps = steps;
+            transcript.entries.push(Entry { steps, event });
+        }
+        Ok(transcript)
+    }
+}
+
+// where a replay first parted from its transcript, the index is the entry
+// where it happened
+#[derive(Debug)]
+pub enum Divergence<W = Memory> {
+    Mismatch(usize, Entry<W>, Entry<W>),
+    Missing(usize, Entry<W>),
+    Extra(usize, Entry<W>),
+    Failed(usize, Error<W>),
+}
+
+impl<W: Word> fmt::Display for Divergence<W> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Divergence::Mismatch(index, expected, actual) => write!(
+                fmt,
+                "entry {}: expected {}, got {}",
+                index, expected, actual
+            ),
+            Divergence::Missing(index, expected) => {
+                write!(fmt, "entry {}: expected {}, got nothing", index, expected)
+            }
+            Divergence::Extra(index, actual) => {
+                write!(fmt, "entry {}: expected nothing, got {}", index, actual)
+            }
+            Divergence::Failed(index, e) => write!(fmt, "entry {}: {}", index, e),
+        }
+    }
+}
+
+impl<W: Word> CPU<W> {
+    // records the i/o from now on, replacing the transcript being recorded
+    pub fn start_transcript(&mut self) {
+        self.transcript = Some(Box::default());
+    }
+
+    pub fn transcript(&self) -> Option<&Transcript<W>> {
+        self.transcript.as_deref()
+    }
+
+    pub fn take_transcript(&mut self) -> Option<Transcript<W>> {
+        self.transcript.take().map(|transcript| *transcript)
+    }
+
+    // runs as many instructions as the transcript covers, feeding it its
+    // inputs, and compares the i/o; the cpu is left recording the replay
+    pub fn replay(&mut self, expected: &Transcript<W>) -> Result<(), Box<Divergence<W>>> {
+        self.start_transcript();
+        self.extend_input(expected.inputs().cloned());
+
+        let error = loop {
+            let steps = self.transcript().map_or(0, Transcript::steps);
+            if steps >= expected.steps() {
+                break None;
+            }
+            match self.step() {
+                Ok(Step::Continue | Step::Output(_)) => {}
+                Ok(Step::NeedInput | Step::Halt) => break None,
+                Err(e) => break Some(e),
+            }
+        };
+
+        let actual = self.transcript().map_or(&[][..], Transcript::entries);
+        for index in 0..expected.len().max(actual.len()) {
+            match (expected.entries.get(index), actual.get(index)) {
+                (Some(expected), Some(actual)) if expected == actual => {}
+                (Some(expected), Some(actual)) => {
+                    return Err(Box::new(Divergence::Mismatch(
+                        index,
+                        expected.clone(),
+                        actual.clone(),
+                    )))
+                }
+                (Some(expected), None) => {
+                    return Err(Box::new(match error {
+                        Some(e) => Divergence::Failed(index, e),
+                        None => Divergence::Missing(index, expected.clone()),
+                    }))
+                }
+                (None, Some(actual)) => {
+                    return Err(Box::new(Divergence::Extra(index, actual.clone())))
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Run};
+
+    const DAY09: &str = include_str!("../../day09/data.txt");
+
+    // outputs a + 10 * b
+    const SUM: &str = r#"3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0"#;
+
+    fn record(program: &str, input: Vec<Memory>) -> Transcript {
+        let mut cpu = CPU::new(parse(program), 0, None);
+        cpu.start_transcript();
+        cpu.extend_input(input);
+        while let Ok(Run::Output(_)) = cpu.run() {}
+        cpu.take_transcript().unwrap()
+    }
+
+    #[test]
+    fn test_record() {
+        let transcript = record(SUM, vec![3, 4]);
+
+        assert_eq!(
+            transcript.to_string(),
+            "# intcode transcript\n1 in 3\n2 in 4\n5 out 43\n6 halt\n"
+        );
+        assert_eq!(transcript.steps(), 6);
+        assert_eq!(transcript.inputs().collect::<Vec<_>>(), vec![&3, &4]);
+        assert_eq!(transcript.outputs().collect::<Vec<_>>(), vec![&43]);
+    }
+
+    #[test]
+    fn test_halt_is_recorded_once() {
+        let mut cpu = CPU::new(parse(SUM), 0, Some(3));
+        cpu.start_transcript();
+        cpu.push_input(4);
+        while let Ok(Run::Output(_)) = cpu.run() {}
+        assert!(matches!(cpu.run(), Ok(Run::Halt)));
+
+        assert_eq!(cpu.take_transcript(), Some(record(SUM, vec![3, 4])));
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut cpu = CPU::new(parse(SUM), 0, None);
+        cpu.enable_journal(10);
+        cpu.start_transcript();
+        cpu.extend_input([3, 4]);
+        while let Ok(Run::Output(_)) = cpu.run() {}
+
+        // back over the output and the second input
+        for _ in 0..4 {
+            assert!(cpu.step_back());
+        }
+        assert_eq!(
+            cpu.transcript().unwrap().to_string(),
+            "# intcode transcript\n1 in 3\n"
+        );
+        assert_eq!(cpu.transcript().unwrap().steps(), 1);
+
+        while let Ok(Run::Output(_)) = cpu.run() {}
+        let transcript = cpu.take_transcript().unwrap();
+        assert_eq!(transcript, record(SUM, vec![3, 4]));
+        assert!(CPU::new(parse(SUM), 0, None).replay(&transcript).is_ok());
+    }
+
+    #[test]
+    fn test_input_on_demand() {
+        // waiting for input costs no instructions
+        let mut cpu = CPU::new(parse(SUM), 0, None);
+        cpu.start_transcript();
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+        cpu.push_input(3);
+        assert!(matches!(cpu.run(), Ok(Run::NeedInput)));
+        cpu.push_input(4);
+        while let Ok(Run::Output(_)) = cpu.run() {}
+
+        assert_eq!(cpu.take_transcript(), Some(record(SUM, vec![3, 4])));
+    }
+
+    #[test]
+    fn test_parse() {
+        let transcript = record(DAY09, vec![1]);
+        let text = transcript.to_string();
+
+        assert_eq!(text.parse::<Transcript>().unwrap(), transcript);
+        assert_eq!(
+            "\n# comment\n 1 in -5 \n"
+                .parse::<Transcript>()
+                .unwrap()
+                .entries(),
+            &[Entry {
+                steps: 1,
+                event: Event::Input(-5)
+            }]
+        );
+
+        let error = |text: &str| text.parse::<Transcript>().unwrap_err().to_string();
+        assert_eq!(error("1 in"), r#"line 1: invalid entry "1 in""#);
+        assert_eq!(error("x halt"), r#"line 1: invalid instruction count "x""#);
+        assert_eq!(error("\n1 out y"), r#"line 2: invalid value "y""#);
+        assert_eq!(
+            error("5 halt\n4 halt"),
+            "line 2: instruction count goes backwards"
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let expected = record(SUM, vec![3, 4]);
+        let replay = |program: &str| CPU::new(parse(program), 0, None).replay(&expected);
+
+        assert!(replay(SUM).is_ok());
+        // multiplies the second value by 11
+        assert_eq!(
+            replay("3,15,3,16,1002,16,11,16,1,16,15,15,4,15,99,0,0")
+                .unwrap_err()
+                .to_string(),
+            "entry 2: expected 5 out 43, got 5 out 47"
+        );
+        // outputs twice
+        assert_eq!(
+            replay("3,15,3,16,4,15,1,16,15,15,4,15,99,0,0")
+                .unwrap_err()
+                .to_string(),
+            "entry 2: expected 5 out 43, got 3 out 3"
+        );
+        // reads one value only
+        assert_eq!(
+            replay("3,17,1101,0,0,18,1002,18,10,18,1,18,17,17,4,17,99,0,0")
+                .unwrap_err()
+                .to_string(),
+            "entry 1: expected 2 in 4, got 5 out 3"
+        );
+        assert!(matches!(
+            *replay("3,15,3,16,3,17,99").unwrap_err(),
+            Divergence::Missing(2, _)
+        ));
+        assert!(matches!(
+            *replay("3,15,3,16,2,-1,10,16,99").unwrap_err(),
+            Divergence::Failed(2, Error::NegativeAddress(..))
+        ));
+    }
+
+    #[test]
+    fn test_replay_prefix() {
+        // a transcript cut short still replays, up to its last entry
+        let mut expected = record(DAY09, vec![2]);
+        expected.entries.truncate(1);
+        expected.steps = expected.entries[0].steps;
+
+        let mut cpu = CPU::new(parse(DAY09), 0, None);
+        assert!(cpu.replay(&expected).is_ok());
+        assert_eq!(cpu.transcript().unwrap().steps(), expected.steps());
+    }
+}
@@ -0,0 +1,607 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+
+use crate::analysis::analyze;
+use crate::device::{Control, IoDevice};
+use crate::disasm::Line;
+use crate::{Error, Memory, Mode, Opcode, Run, Step, CPU};
+
+const VALUES_PER_LINE: usize = 16;
+const NONE: usize = usize::MAX;
+
+// how a translated block hands control back to the machine
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Jump(usize),
+    NeedInput(usize),
+    Output(Memory, usize),
+    Halt(usize),
+}
+
+// runs the block starting at ip, None when there is no block there; a
+// block fails with the address of the instruction the interpreter must
+// execute instead, before that instruction changed anything
+pub type Dispatch = fn(&mut Machine, usize) -> Option<Result<Exit, usize>>;
+
+// a cpu that runs the translated blocks of its program and falls back to
+// the interpreter for the code the translation does not cover, for the
+// blocks the program wrote into and for the instructions that fail
+pub struct Machine {
+    cpu: CPU,
+    dispatch: Dispatch,
+    // the block that holds each translated word
+    owner: Vec<usize>,
+    stale: Vec<bool>,
+}
+
+impl Machine {
+    pub fn new(program: &[Memory], blocks: &[(usize, usize)], dispatch: Dispatch) -> Self {
+        let len = blocks.iter().map(|&(_, end)| end).max().unwrap_or(0);
+        let mut owner = vec![NONE; len];
+        for &(start, end) in blocks {
+            owner[start..end]
+                .iter_mut()
+                .for_each(|owner| *owner = start);
+        }
+
+        let mut cpu = CPU::new(program.to_vec(), 0, None);
+        // tells the interpreter fallback which cell an instruction wrote
+        cpu.enable_journal(1);
+
+        Self {
+            cpu,
+            dispatch,
+            owner,
+            stale: vec![false; len],
+        }
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn ip(&self) -> usize {
+        self.cpu.ip
+    }
+
+    pub fn memory(&self, address: usize) -> Memory {
+        self.cpu.memory(address)
+    }
+
+    pub fn poke(&mut self, address: usize, value: Memory) {
+        self.store(address, value);
+    }
+
+    pub fn push_input(&mut self, value: Memory) {
+        self.cpu.push_input(value);
+    }
+
+    pub fn extend_input<I: IntoIterator<Item = Memory>>(&mut self, input: I) {
+        self.cpu.extend_input(input);
+    }
+
+    pub fn fork(&self) -> Self {
+        Self {
+            cpu: self.cpu.fork(),
+            dispatch: self.dispatch,
+            owner: self.owner.clone(),
+            stale: self.stale.clone(),
+        }
+    }
+
+    pub fn run(&mut self) -> Result<Run, Error> {
+        loop {
+            let ip = self.cpu.ip;
+            let native = if self.stale.get(ip) == Some(&true) {
+                None
+            } else {
+                (self.dispatch)(self, ip)
+            };
+
+            match native {
+                Some(Ok(Exit::Jump(next))) => self.cpu.ip = next,
+                Some(Ok(Exit::Output(value, next))) => {
+                    self.cpu.ip = next;
+                    return Ok(Run::Output(value));
+                }
+                Some(Ok(Exit::NeedInput(ip))) => {
+                    self.cpu.ip = ip;
+                    return Ok(Run::NeedInput);
+                }
+                Some(Ok(Exit::Halt(ip))) => {
+                    self.cpu.ip = ip;
+                    return Ok(Run::Halt);
+                }
+                Some(Err(ip)) => {
+                    self.cpu.ip = ip;
+                    if let Some(run) = self.interpret()? {
+                        return Ok(run);
+                    }
+                }
+                None => {
+                    if let Some(run) = self.interpret()? {
+                        return Ok(run);
+                    }
+                }
+            }
+        }
+    }
+
+    // like CPU::run_with
+    pub fn run_with<D: IoDevice>(&mut self, device: &mut D) -> Result<Run, Error> {
+        loop {
+            match self.run()? {
+                Run::NeedInput => match device.input() {
+                    Some(value) => self.push_input(value),
+                    None => break Ok(Run::NeedInput),
+                },
+                Run::Output(value) => {
+                    if device.output(value) == Control::Pause {
+                        break Ok(Run::Output(value));
+                    }
+                }
+                Run::Halt => {
+                    device.halt();
+                    break Ok(Run::Halt);
+                }
+            }
+        }
+    }
+
+    fn interpret(&mut self) -> Result<Option<Run>, Error> {
+        let step = self.cpu.step()?;
+        if let Some((address, old)) = self
+            .cpu
+            .journal
+            .as_mut()
+            .and_then(|journal| journal.take_write())
+        {
+            self.touched(address, old);
+        }
+
+        Ok(match step {
+            Step::Continue => None,
+            Step::NeedInput => Some(Run::NeedInput),
+            Step::Output(value) => Some(Run::Output(value)),
+            Step::Halt => Some(Run::Halt),
+        })
+    }
+
+    #[inline(always)]
+    fn touched(&mut self, address: usize, old: Memory) -> bool {
+        match self.owner.get(address) {
+            Some(&start) if start != NONE && self.cpu.memory[address] != old => {
+                self.stale[start] = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // the operations the translated code is made of
+
+    #[inline(always)]
+    pub fn load(&self, address: usize) -> Memory {
+        self.cpu.memory[address]
+    }
+
+    // true when the write changed translated code, the block must stop
+    #[inline(always)]
+    pub fn store(&mut self, address: usize, value: Memory) -> bool {
+        let old = self.cpu.memory[address];
+        self.cpu.store(address, value);
+        self.touched(address, old)
+    }
+
+    #[inline(always)]
+    pub fn relative(&self, offset: Memory, ip: usize) -> Result<usize, usize> {
+        self.cpu
+            .base
+            .checked_add(offset)
+            .and_then(|address| usize::try_from(address).ok())
+            .ok_or(ip)
+    }
+
+    #[inline(always)]
+    pub fn adjust_base(&mut self, offset: Memory, ip: usize) -> Result<(), usize> {
+        self.cpu.base = self.cpu.base.checked_add(offset).ok_or(ip)?;
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn target(&self, value: Memory, ip: usize) -> Result<usize, usize> {
+        usize::try_from(value).map_err(|_| ip)
+    }
+
+    #[inline(always)]
+    pub fn read_input(&mut self) -> Option<Memory> {
+        self.cpu.input.pop_front()
+    }
+}
+
+// the analysis blocks, also split before each input and after each output
+// so that the machine can resume at a block start after any i/o
+fn split(program: &[Memory]) -> Vec<Vec<Line>> {
+    let mut blocks = vec![];
+    for block in analyze(program).blocks() {
+        let mut lines: Vec<Line> = vec![];
+        for line in &block.lines {
+            let opcode = match line {
+                Line::Instruction { opcode, .. } => *opcode,
+                Line::Data { .. } => unreachable!(),
+            };
+            if matches!(opcode, Opcode::Input(_)) && !lines.is_empty() {
+                blocks.push(std::mem::take(&mut lines));
+            }
+            lines.push(line.clone());
+            if matches!(opcode, Opcode::Output(_)) {
+                blocks.push(std::mem::take(&mut lines));
+            }
+        }
+        if !lines.is_empty() {
+            blocks.push(lines);
+        }
+    }
+    blocks
+}
+
+// None for positions that are not addresses, the generated code would not
+// compile
+fn read(mode: Mode, value: Memory, ip: usize) -> Option<String> {
+    match mode {
+        Mode::Position => Some(format!("m.load({})", usize::try_from(value).ok()?)),
+        Mode::Immediate => Some(value.to_string()),
+        Mode::Relative => Some(format!("m.load(m.relative({}, {})?)", value, ip)),
+    }
+}
+
+fn address(mode: Mode, value: Memory, ip: usize) -> Option<String> {
+    match mode {
+        Mode::Position => Some(usize::try_from(value).ok()?.to_string()),
+        Mode::Relative => Some(format!("m.relative({}, {})?", value, ip)),
+        Mode::Immediate => None,
+    }
+}
+
+// the statements of one instruction, and the expression that ends the
+// block when the instruction is the last one; None when the instruction
+// is left to the interpreter
+fn instruction(
+    ip: usize,
+    opcode: &Opcode,
+    parameters: &[Memory],
+) -> Option<(Vec<String>, Option<String>)> {
+    let next = ip + parameters.len() + 1;
+    let modes = opcode.modes();
+    let operand = |index: usize| read(modes[index], parameters[index], ip);
+    let store = |index: usize, value: &str| {
+        Some(vec![
+            format!(
+                "let address = {};",
+                address(modes[index], parameters[index], ip)?
+            ),
+            format!(
+                "if m.store(address, {}) {{ return Ok(Exit::Jump({})); }}",
+                value, next
+            ),
+        ])
+    };
+    let binary = |operation: String| {
+        let mut statements = vec![
+            format!("let a: Memory = {};", operand(0)?),
+            format!("let b: Memory = {};", operand(1)?),
+            format!("let value = {};", operation),
+        ];
+        statements.extend(store(2, "value")?);
+        Some((statements, None))
+    };
+
+    match *opcode {
+        Opcode::Add(..) => binary(format!("a.checked_add(b).ok_or({}_usize)?", ip)),
+        Opcode::Mul(..) => binary(format!("a.checked_mul(b).ok_or({}_usize)?", ip)),
+        Opcode::IfLess(..) => binary("Memory::from(a < b)".to_string()),
+        Opcode::IfEqTo(..) => binary("Memory::from(a == b)".to_string()),
+        Opcode::Input(_) => {
+            // the address comes first, an input is not taken by an
+            // instruction that is left to the interpreter
+            let mut statements = store(0, "value")?;
+            statements.insert(
+                1,
+                format!(
+                    "let value = match m.read_input() {{ Some(value) => value, None => return Ok(Exit::NeedInput({})) }};",
+                    ip
+                ),
+            );
+            Some((statements, None))
+        }
+        Opcode::Output(_) => Some((
+            vec![],
+            Some(format!("Ok(Exit::Output({}, {}))", operand(0)?, next)),
+        )),
+        Opcode::IfNEq(condition, target) | Opcode::IfEq(condition, target) => {
+            let nonzero = matches!(opcode, Opcode::IfNEq(..));
+            let jump = match (target, usize::try_from(parameters[1])) {
+                (Mode::Immediate, Ok(target)) => format!("Ok(Exit::Jump({}))", target),
+                _ => format!("Ok(Exit::Jump(m.target({}, {})?))", operand(1)?, ip),
+            };
+
+            match condition {
+                Mode::Immediate if (parameters[0] != 0) == nonzero => Some((vec![], Some(jump))),
+                Mode::Immediate => Some((vec![], None)),
+                _ => Some((
+                    vec![format!(
+                        "if {} {} 0 {{ return {}; }}",
+                        operand(0)?,
+                        if nonzero { "!=" } else { "==" },
+                        jump
+                    )],
+                    None,
+                )),
+            }
+        }
+        Opcode::Base(_) => Some((
+            vec![format!("m.adjust_base({}, {})?;", operand(0)?, ip)],
+            None,
+        )),
+        Opcode::Halt => Some((vec![], Some(format!("Ok(Exit::Halt({}))", ip)))),
+    }
+}
+
+fn block(code: &mut String, lines: &[Line]) -> fmt::Result {
+    let start = lines[0].address();
+    let mut body = String::new();
+    let mut exit = None;
+    for line in lines {
+        let (ip, opcode, parameters) = match line {
+            Line::Instruction {
+                address,
+                opcode,
+                parameters,
+            } => (*address, opcode, parameters),
+            Line::Data { .. } => unreachable!(),
+        };
+
+        writeln!(body, "    // {}: {}", ip, line)?;
+        match instruction(ip, opcode, parameters) {
+            Some((statements, end)) => {
+                for statement in statements {
+                    writeln!(body, "    {}", statement)?;
+                }
+                if end.is_some() {
+                    exit = end;
+                    break;
+                }
+            }
+            None => {
+                exit = Some(format!("Err({})", ip));
+                break;
+            }
+        }
+    }
+    let exit = exit.unwrap_or_else(|| {
+        let last = lines.last().unwrap();
+        format!("Ok(Exit::Jump({}))", last.address() + last.words())
+    });
+
+    let machine = if body.contains("m.") || exit.contains("m.") {
+        "m"
+    } else {
+        "_m"
+    };
+    writeln!(code)?;
+    writeln!(
+        code,
+        "fn block_{}({}: &mut Machine) -> Result<Exit, usize> {{",
+        start, machine
+    )?;
+    code.push_str(&body);
+    writeln!(code, "    {}", exit)?;
+    writeln!(code, "}}")
+}
+
+// rust source for a module that runs the program natively: the module has
+// the program, its translated blocks and a machine() constructor
+pub fn translate(program: &[Memory]) -> String {
+    let blocks = split(program);
+    let mut code = String::new();
+
+    writeln!(code, "// translated by intcode::translate, do not edit").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "use intcode::translate::{{Exit, Machine}};").unwrap();
+    writeln!(code, "use intcode::Memory;").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(code, "pub const PROGRAM: [Memory; {}] = [", program.len()).unwrap();
+    for values in program.chunks(VALUES_PER_LINE) {
+        let values = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        writeln!(code, "    {},", values.join(", ")).unwrap();
+    }
+    writeln!(code, "];").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(
+        code,
+        "pub const BLOCKS: [(usize, usize); {}] = [",
+        blocks.len()
+    )
+    .unwrap();
+    for lines in &blocks {
+        let last = lines.last().unwrap();
+        writeln!(
+            code,
+            "    ({}, {}),",
+            lines[0].address(),
+            last.address() + last.words()
+        )
+        .unwrap();
+    }
+    writeln!(code, "];").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(code, "pub fn machine() -> Machine {{").unwrap();
+    writeln!(code, "    Machine::new(&PROGRAM, &BLOCKS, dispatch)").unwrap();
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(
+        code,
+        "fn dispatch(m: &mut Machine, ip: usize) -> Option<Result<Exit, usize>> {{"
+    )
+    .unwrap();
+    writeln!(code, "    Some(match ip {{").unwrap();
+    for lines in &blocks {
+        let start = lines[0].address();
+        writeln!(code, "        {} => block_{}(m),", start, start).unwrap();
+    }
+    writeln!(code, "        _ => return None,").unwrap();
+    writeln!(code, "    }})").unwrap();
+    writeln!(code, "}}").unwrap();
+
+    for lines in &blocks {
+        block(&mut code, lines).unwrap();
+    }
+
+    code
+}
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod fixture;
+
+#[cfg(test)]
+#[rustfmt::skip]
+mod relative_input;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::parse;
+
+    // sums the squares of 1..=n, squaring in a subroutine; the sum is
+    // written into the code of an output and picks a computed jump
+    const SOURCE: &str = r"
+        arb  #stack
+        in   [n]
+loop:   add  [n], #0, rb+1
+        call #square
+        out  rb+1
+        add  [sum], rb+1, [sum]
+        add  [n], #-1, [n]
+        jnz  [n], #loop
+        add  [sum], #0, [patch+1]
+patch:  out  #0
+        lt   [sum], #100, [small]
+        jz   [small], [far]
+        out  #1
+        hlt
+big:    out  #2
+        hlt
+square: mul  rb+1, rb+1, rb+1
+        ret
+n:      data 0
+sum:    data 0
+small:  data 0
+far:    data big
+stack:  data 0
+";
+
+    const DAY09: &str = include_str!("../../day09/data.txt");
+
+    fn outputs<F: FnMut() -> Result<Run, Error>>(mut run: F) -> Result<Vec<Memory>, Error> {
+        let mut outputs = vec![];
+        loop {
+            match run()? {
+                Run::Output(value) => outputs.push(value),
+                _ => break Ok(outputs),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixture() {
+        let program = assemble(SOURCE).unwrap();
+
+        assert_eq!(program, fixture::PROGRAM);
+        assert_eq!(translate(&program), include_str!("translate/fixture.rs"));
+    }
+
+    #[test]
+    fn test_same_as_interpreter() {
+        for n in (1..=12).chain(Some(Memory::MAX / 2)) {
+            let mut machine = fixture::machine();
+            machine.push_input(n);
+            let mut cpu = CPU::new(fixture::PROGRAM.to_vec(), 0, Some(n));
+
+            let expected = outputs(|| cpu.run());
+            assert_eq!(
+                format!("{:?}", outputs(|| machine.run())),
+                format!("{:?}", expected)
+            );
+            assert_eq!(machine.ip(), cpu.ip());
+            assert!(machine.cpu().diff(&cpu).is_empty());
+        }
+
+        let mut machine = fixture::machine();
+        machine.push_input(3);
+        assert_eq!(outputs(|| machine.run()).unwrap(), vec![9, 4, 1, 14, 1]);
+        // only the block with the patched output is left to the interpreter
+        assert_eq!(machine.stale.iter().filter(|&&stale| stale).count(), 1);
+    }
+
+    #[test]
+    fn test_need_input() {
+        let mut machine = fixture::machine();
+
+        assert!(matches!(machine.run(), Ok(Run::NeedInput)));
+        assert_eq!(machine.ip(), 2);
+
+        machine.push_input(7);
+        assert!(matches!(machine.run(), Ok(Run::Output(49))));
+    }
+
+    #[test]
+    fn test_relative_input() {
+        let program = [109, -5, 203, 0, 99];
+        assert_eq!(program, relative_input::PROGRAM);
+        assert_eq!(
+            translate(&program),
+            include_str!("translate/relative_input.rs")
+        );
+
+        // the address is negative, the interpreter fails before it takes
+        // the input
+        let mut machine = relative_input::machine();
+        machine.push_input(7);
+
+        assert!(machine.run().is_err());
+        assert_eq!(machine.ip(), 2);
+        assert_eq!(machine.cpu().input(), Some(7));
+    }
+
+    #[test]
+    fn test_huge_immediates() {
+        // the push overflows and the position is not an address, which is
+        // left to the interpreter so that the module still compiles
+        let code = translate(&[21101, Memory::MAX, 1, 0, 1001, 1 << 64, 1, 0, 99]);
+
+        assert!(code.contains("a.checked_add(b).ok_or(0_usize)?"));
+        assert!(code.contains("    Err(4)\n"));
+        assert!(!code.contains("m.load(18446744073709551616)"));
+    }
+
+    #[test]
+    fn test_interpreter_only() {
+        fn nothing(_: &mut Machine, _: usize) -> Option<Result<Exit, usize>> {
+            None
+        }
+
+        let mut machine = Machine::new(&parse(DAY09), &[], nothing);
+        machine.push_input(1);
+
+        assert_eq!(outputs(|| machine.run()).unwrap(), vec![2457252183]);
+    }
+}
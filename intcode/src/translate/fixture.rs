@@ -0,0 +1,142 @@
+// translated by intcode::translate, do not edit
+
+use intcode::translate::{Exit, Machine};
+use intcode::Memory;
+
+pub const PROGRAM: [Memory; 59] = [
+    109, 58, 3, 54, 21001, 54, 0, 1, 21101, 15, 0, 0, 1106, 0, 47, 204,
+    1, 2001, 55, 1, 55, 1001, 54, -1, 54, 1005, 54, 4, 1001, 55, 0, 33,
+    104, 0, 1007, 55, 100, 56, 6, 56, 57, 104, 1, 99, 104, 2, 99, 22202,
+    1, 1, 1, 2106, 0, 0, 0, 0, 0, 44, 0,
+];
+
+pub const BLOCKS: [(usize, usize); 10] = [
+    (0, 2),
+    (2, 4),
+    (4, 15),
+    (15, 17),
+    (17, 28),
+    (28, 34),
+    (34, 41),
+    (41, 43),
+    (43, 44),
+    (47, 54),
+];
+
+pub fn machine() -> Machine {
+    Machine::new(&PROGRAM, &BLOCKS, dispatch)
+}
+
+fn dispatch(m: &mut Machine, ip: usize) -> Option<Result<Exit, usize>> {
+    Some(match ip {
+        0 => block_0(m),
+        2 => block_2(m),
+        4 => block_4(m),
+        15 => block_15(m),
+        17 => block_17(m),
+        28 => block_28(m),
+        34 => block_34(m),
+        41 => block_41(m),
+        43 => block_43(m),
+        47 => block_47(m),
+        _ => return None,
+    })
+}
+
+fn block_0(m: &mut Machine) -> Result<Exit, usize> {
+    // 0: arb #58
+    m.adjust_base(58, 0)?;
+    Ok(Exit::Jump(2))
+}
+
+fn block_2(m: &mut Machine) -> Result<Exit, usize> {
+    // 2: in  [54]
+    let address = 54;
+    let value = match m.read_input() { Some(value) => value, None => return Ok(Exit::NeedInput(2)) };
+    if m.store(address, value) { return Ok(Exit::Jump(4)); }
+    Ok(Exit::Jump(4))
+}
+
+fn block_4(m: &mut Machine) -> Result<Exit, usize> {
+    // 4: add [54], #0, rb+1
+    let a: Memory = m.load(54);
+    let b: Memory = 0;
+    let value = a.checked_add(b).ok_or(4_usize)?;
+    let address = m.relative(1, 4)?;
+    if m.store(address, value) { return Ok(Exit::Jump(8)); }
+    // 8: add #15, #0, rb+0
+    let a: Memory = 15;
+    let b: Memory = 0;
+    let value = a.checked_add(b).ok_or(8_usize)?;
+    let address = m.relative(0, 8)?;
+    if m.store(address, value) { return Ok(Exit::Jump(12)); }
+    // 12: jz  #0, #47
+    Ok(Exit::Jump(47))
+}
+
+fn block_15(m: &mut Machine) -> Result<Exit, usize> {
+    // 15: out rb+1
+    Ok(Exit::Output(m.load(m.relative(1, 15)?), 17))
+}
+
+fn block_17(m: &mut Machine) -> Result<Exit, usize> {
+    // 17: add [55], rb+1, [55]
+    let a: Memory = m.load(55);
+    let b: Memory = m.load(m.relative(1, 17)?);
+    let value = a.checked_add(b).ok_or(17_usize)?;
+    let address = 55;
+    if m.store(address, value) { return Ok(Exit::Jump(21)); }
+    // 21: add [54], #-1, [54]
+    let a: Memory = m.load(54);
+    let b: Memory = -1;
+    let value = a.checked_add(b).ok_or(21_usize)?;
+    let address = 54;
+    if m.store(address, value) { return Ok(Exit::Jump(25)); }
+    // 25: jnz [54], #4
+    if m.load(54) != 0 { return Ok(Exit::Jump(4)); }
+    Ok(Exit::Jump(28))
+}
+
+fn block_28(m: &mut Machine) -> Result<Exit, usize> {
+    // 28: add [55], #0, [33]
+    let a: Memory = m.load(55);
+    let b: Memory = 0;
+    let value = a.checked_add(b).ok_or(28_usize)?;
+    let address = 33;
+    if m.store(address, value) { return Ok(Exit::Jump(32)); }
+    // 32: out #0
+    Ok(Exit::Output(0, 34))
+}
+
+fn block_34(m: &mut Machine) -> Result<Exit, usize> {
+    // 34: lt  [55], #100, [56]
+    let a: Memory = m.load(55);
+    let b: Memory = 100;
+    let value = Memory::from(a < b);
+    let address = 56;
+    if m.store(address, value) { return Ok(Exit::Jump(38)); }
+    // 38: jz  [56], [57]
+    if m.load(56) == 0 { return Ok(Exit::Jump(m.target(m.load(57), 38)?)); }
+    Ok(Exit::Jump(41))
+}
+
+fn block_41(_m: &mut Machine) -> Result<Exit, usize> {
+    // 41: out #1
+    Ok(Exit::Output(1, 43))
+}
+
+fn block_43(_m: &mut Machine) -> Result<Exit, usize> {
+    // 43: hlt
+    Ok(Exit::Halt(43))
+}
+
+fn block_47(m: &mut Machine) -> Result<Exit, usize> {
+    // 47: mul rb+1, rb+1, rb+1
+    let a: Memory = m.load(m.relative(1, 47)?);
+    let b: Memory = m.load(m.relative(1, 47)?);
+    let value = a.checked_mul(b).ok_or(47_usize)?;
+    let address = m.relative(1, 47)?;
+    if m.store(address, value) { return Ok(Exit::Jump(51)); }
+    // 51: jz  #0, rb+0
+    Ok(Exit::Jump(m.target(m.load(m.relative(0, 51)?), 51)?))
+}
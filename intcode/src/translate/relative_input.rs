@@ -0,0 +1,40 @@
+// translated by intcode::translate, do not edit
+
+use intcode::translate::{Exit, Machine};
+use intcode::Memory;
+
+pub const PROGRAM: [Memory; 5] = [
+    109, -5, 203, 0, 99,
+];
+
+pub const BLOCKS: [(usize, usize); 2] = [
+    (0, 2),
+    (2, 5),
+];
+
+pub fn machine() -> Machine {
+    Machine::new(&PROGRAM, &BLOCKS, dispatch)
+}
+
+fn dispatch(m: &mut Machine, ip: usize) -> Option<Result<Exit, usize>> {
+    Some(match ip {
+        0 => block_0(m),
+        2 => block_2(m),
+        _ => return None,
+    })
+}
+
+fn block_0(m: &mut Machine) -> Result<Exit, usize> {
+    // 0: arb #-5
+    m.adjust_base(-5, 0)?;
+    Ok(Exit::Jump(2))
+}
+
+fn block_2(m: &mut Machine) -> Result<Exit, usize> {
+    // 2: in  rb+0
+    let address = m.relative(0, 2)?;
+    let value = match m.read_input() { Some(value) => value, None => return Ok(Exit::NeedInput(2)) };
+    if m.store(address, value) { return Ok(Exit::Jump(4)); }
+    // 4: hlt
+    Ok(Exit::Halt(4))
+}
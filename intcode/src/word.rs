@@ -0,0 +1,144 @@
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+#[cfg(feature = "bigint")]
+pub use num_bigint::BigInt;
+
+// what add and mul do when the result does not fit in the word
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Overflow {
+    #[default]
+    Trap,
+    Wrap,
+    Saturate,
+}
+
+// the value stored in a memory cell
+pub trait Word:
+    Clone
+    + Default
+    + PartialEq
+    + PartialOrd
+    + Hash
+    + From<i32>
+    + FromStr
+    + fmt::Debug
+    + fmt::Display
+    + Send
+    + Sync
+    + 'static
+{
+    // None when the result overflows and the policy is to trap
+    fn sum(&self, other: &Self, overflow: Overflow) -> Option<Self>;
+
+    fn product(&self, other: &Self, overflow: Overflow) -> Option<Self>;
+
+    fn to_i64(&self) -> Option<i64>;
+
+    fn to_usize(&self) -> Option<usize>;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn is_negative(&self) -> bool {
+        *self < Self::default()
+    }
+}
+
+macro_rules! primitive_word {
+    ($($word:ty),*) => {
+        $(
+            impl Word for $word {
+                #[inline(always)]
+                fn sum(&self, other: &Self, overflow: Overflow) -> Option<Self> {
+                    match overflow {
+                        Overflow::Trap => self.checked_add(*other),
+                        Overflow::Wrap => Some(self.wrapping_add(*other)),
+                        Overflow::Saturate => Some(self.saturating_add(*other)),
+                    }
+                }
+
+                #[inline(always)]
+                fn product(&self, other: &Self, overflow: Overflow) -> Option<Self> {
+                    match overflow {
+                        Overflow::Trap => self.checked_mul(*other),
+                        Overflow::Wrap => Some(self.wrapping_mul(*other)),
+                        Overflow::Saturate => Some(self.saturating_mul(*other)),
+                    }
+                }
+
+                #[inline(always)]
+                fn to_i64(&self) -> Option<i64> {
+                    i64::try_from(*self).ok()
+                }
+
+                #[inline(always)]
+                fn to_usize(&self) -> Option<usize> {
+                    usize::try_from(*self).ok()
+                }
+            }
+        )*
+    };
+}
+
+primitive_word!(i64, i128);
+
+// never overflows, so the policy does not apply
+#[cfg(feature = "bigint")]
+impl Word for BigInt {
+    fn sum(&self, other: &Self, _: Overflow) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn product(&self, other: &Self, _: Overflow) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        i64::try_from(self).ok()
+    }
+
+    fn to_usize(&self) -> Option<usize> {
+        usize::try_from(self).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(i64::MAX.sum(&1, Overflow::Trap), None);
+        assert_eq!(i64::MAX.sum(&1, Overflow::Wrap), Some(i64::MIN));
+        assert_eq!(i64::MAX.sum(&1, Overflow::Saturate), Some(i64::MAX));
+        assert_eq!(i128::MIN.product(&2, Overflow::Trap), None);
+        assert_eq!(i128::MIN.product(&2, Overflow::Wrap), Some(0));
+        assert_eq!(i128::MIN.product(&2, Overflow::Saturate), Some(i128::MIN));
+        assert_eq!(3i64.product(&-4, Overflow::Trap), Some(-12));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(i128::MAX.to_i64(), None);
+        assert_eq!((-1i64).to_usize(), None);
+        assert_eq!(42i128.to_usize(), Some(42));
+        assert!((-1i64).is_negative());
+        assert!(0i128.is_zero());
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint() {
+        let big = BigInt::from(i128::MAX);
+
+        assert_eq!(
+            big.product(&big, Overflow::Trap).unwrap().to_string(),
+            "28948022309329048855892746252171976962977213799489202546401021394546514198529"
+        );
+        assert_eq!(big.to_i64(), None);
+        assert_eq!(BigInt::from(7).to_usize(), Some(7));
+    }
+}
@@ -0,0 +1,194 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// a file in the temp dir, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path = env::temp_dir().join(format!("intcode-runner-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// the exit status and stdout of the runner
+fn run(args: &[&str], stdin: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_intcode"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+// adds the two values it reads
+const SUM: &str = "3,11,3,12,1,11,12,13,4,13,99,0,0,0";
+
+#[test]
+fn test_halt() {
+    let program = TempFile::new("halt", "104,7,104,-1,99");
+
+    assert_eq!(run(&[program.path()], ""), (0, "7\n-1\n".to_string()));
+}
+
+#[test]
+fn test_error() {
+    let program = TempFile::new("error", "104,7,42");
+
+    assert_eq!(run(&[program.path()], ""), (1, "7\n".to_string()));
+    assert_eq!(run(&["/nonexistent/program"], "").0, 1);
+}
+
+#[test]
+fn test_usage() {
+    let program = TempFile::new("usage", "99");
+
+    assert_eq!(run(&[], "").0, 2);
+    assert_eq!(run(&["--unknown", program.path()], "").0, 2);
+    assert_eq!(run(&[program.path(), "--budget"], "").0, 2);
+    assert_eq!(run(&[program.path(), "--budget", "x"], "").0, 2);
+    assert_eq!(
+        run(
+            &[program.path(), "--patch", &format!("{}=1,2", usize::MAX)],
+            ""
+        )
+        .0,
+        2
+    );
+}
+
+#[test]
+fn test_input() {
+    let program = TempFile::new("input", SUM);
+
+    assert_eq!(run(&[program.path()], "3\n\n4\n"), (0, "7\n".to_string()));
+    // the values of --input come first
+    assert_eq!(
+        run(&[program.path(), "--input", "30"], "4\n"),
+        (0, "34\n".to_string())
+    );
+    assert_eq!(
+        run(&[program.path(), "--input", "30,40"], ""),
+        (0, "70\n".to_string())
+    );
+    assert_eq!(run(&[program.path()], "x\n").0, 1);
+}
+
+#[test]
+fn test_out_of_input() {
+    let program = TempFile::new("out-of-input", SUM);
+
+    assert_eq!(run(&[program.path()], "3\n").0, 3);
+    assert_eq!(run(&[program.path(), "--input", "3"], "").0, 3);
+}
+
+#[test]
+fn test_ascii() {
+    // echoes a line, then prints a value that is not ascii
+    let program = TempFile::new(
+        "ascii",
+        "3,100,4,100,1008,100,10,101,1006,101,0,104,1000,99",
+    );
+
+    assert_eq!(
+        run(&[program.path(), "--ascii"], "Hi\n"),
+        (0, "Hi\n1000\n".to_string())
+    );
+    assert_eq!(
+        run(&[program.path(), "--ascii"], "tàke\n"),
+        (1, String::new())
+    );
+}
+
+#[test]
+fn test_patch() {
+    let program = TempFile::new("patch", "104,7,104,8,99");
+
+    assert_eq!(
+        run(&[program.path(), "--patch", "1=70"], ""),
+        (0, "70\n8\n".to_string())
+    );
+    // entries of several options are merged, the last value wins
+    assert_eq!(
+        run(
+            &[program.path(), "--patch", "1=70 3=80", "--patch", "3=90"],
+            ""
+        ),
+        (0, "70\n90\n".to_string())
+    );
+}
+
+#[test]
+fn test_budget() {
+    // outputs 1 then loops forever
+    let program = TempFile::new("budget", "104,1,1105,1,2");
+
+    assert_eq!(
+        run(&[program.path(), "--budget", "100"], ""),
+        (4, "1\n".to_string())
+    );
+    assert_eq!(
+        run(&[program.path(), "--budget", "0"], ""),
+        (4, String::new())
+    );
+
+    let program = TempFile::new("budget-halt", "104,1,99");
+    assert_eq!(
+        run(&[program.path(), "--budget", "2"], ""),
+        (0, "1\n".to_string())
+    );
+}
+
+#[test]
+fn test_record_replay() {
+    let program = TempFile::new("record", SUM);
+    let transcript = TempFile::new("record.txt", "");
+
+    assert_eq!(
+        run(&[program.path(), "--record", transcript.path()], "3\n4\n"),
+        (0, "7\n".to_string())
+    );
+    assert_eq!(
+        fs::read_to_string(transcript.path()).unwrap(),
+        "# intcode transcript\n1 in 3\n2 in 4\n4 out 7\n5 halt\n"
+    );
+
+    assert_eq!(
+        run(&[program.path(), "--replay", transcript.path()], ""),
+        (0, String::new())
+    );
+
+    // multiplies instead of adding
+    let other = TempFile::new("replay-other", "3,11,3,12,2,11,12,13,4,13,99,0,0,0");
+    assert_eq!(run(&[other.path(), "--replay", transcript.path()], "").0, 5);
+
+    let invalid = TempFile::new("replay-invalid", "1 in");
+    assert_eq!(run(&[program.path(), "--replay", invalid.path()], "").0, 1);
+}